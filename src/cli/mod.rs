@@ -0,0 +1,708 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path;
+use std::os::unix::fs::MetadataExt;
+
+use crate::fs as ftw;
+use crate::process;
+use crate::process::Process;
+use crate::user;
+
+const USAGE: &str = "Usage: qwe <command> [args]
+
+Commands:
+    ps [-u USER]            list processes, optionally owned by USER
+    pstree [-c] [-u] [PID]  show processes as a tree
+    fuser [-m] PATH         show processes which opened PATH
+    lsof [PID]              list files opened by processes
+    netstat                 list sockets with processes holding them
+    top [-n N] [-d SECONDS] show processes using most CPU
+    getent DATABASE [KEY]   look up entries of passwd, group or shadow databases
+    id [USER]               show user and group ids of USER or the current user
+    ftw [-d] [-p] [-m] PATH walk the file tree rooted at PATH
+    stat PATH...            show metadata of files
+    help [COMMAND]          show this message or help of COMMAND";
+
+const PS_USAGE: &str = "Usage: qwe ps [-u USER]
+
+Prints pid, parent pid, user and name of every process.
+    -u USER    show only processes owned by USER, either a user name or a uid";
+
+const PSTREE_USAGE: &str = "Usage: qwe pstree [-c] [-u] [PID]
+
+Prints processes as a tree built from their parent pids, only the subtree of PID if it's given.
+Processes which parent has exited are shown as roots.
+    -c    omit pids and merge identical subtrees
+    -u    show the user of a process if it differs from the one of its parent";
+
+const FUSER_USAGE: &str = "Usage: qwe fuser [-m] PATH
+
+Prints pid, name and descriptor of every process which opened PATH or its hard link.
+    -m    show processes which opened any file on the file system of PATH";
+
+const LSOF_USAGE: &str = "Usage: qwe lsof [PID]
+
+Prints descriptors, kinds, offsets and targets of files opened by PID or by every process
+whose descriptors can be read.";
+
+const NETSTAT_USAGE: &str = "Usage: qwe netstat
+
+Prints protocol, addresses, state, pid and name of every socket held by a process whose
+descriptors can be read.";
+
+const TOP_USAGE: &str = "Usage: qwe top [-n N] [-d SECONDS]
+
+Samples processes twice and prints N processes using most CPU between the samples.
+    -n N          number of processes, 10 by default
+    -d SECONDS    delay between the samples, 1 by default";
+
+const GETENT_USAGE: &str = "Usage: qwe getent DATABASE [KEY]
+
+Prints entries of DATABASE, all of them without KEY. DATABASE is one of
+    passwd    KEY is either a user name or a uid
+    group     KEY is either a group name or a gid
+    shadow    KEY is a user name, reading the database usually needs root privileges";
+
+const ID_USAGE: &str = "Usage: qwe id [USER]
+
+Prints uid, primary gid and supplementary groups of USER or of the current user. USER is either
+a user name or a uid.";
+
+const FTW_USAGE: &str = "Usage: qwe ftw [-d] [-p] [-m] PATH
+
+Prints type, depth and path of every entry under PATH.
+    -d    report a directory after its content
+    -p    do not follow symbolic links
+    -m    stay on the file system of PATH";
+
+const STAT_USAGE: &str = "Usage: qwe stat PATH...
+
+Prints metadata of every PATH.";
+
+/// Maximum number of directories the file tree walker keeps open.
+const FTW_NOPENFD: usize = 20;
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum CliError {
+    Usage(String),
+    Failure(String),
+}
+
+impl CliError {
+    /// Exit code of the program. Misuse of the command line is distinguished from failures of a
+    /// command itself.
+    pub fn code(&self) -> i32 {
+        match self {
+            CliError::Failure(_) => 1,
+            CliError::Usage(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(x) => write!(f, "{}\n\n{}", x, USAGE),
+            CliError::Failure(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+type CliResult = Result<(), CliError>;
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Command {
+    Help(Option<String>),
+    Ps { user: Option<String> },
+    Pstree { pid: Option<u32>, compact: bool, users: bool },
+    Fuser { path: String, mount: bool },
+    Lsof { pid: Option<u32> },
+    Netstat,
+    Top { count: usize, delay: f64 },
+    Getent { database: Database, key: Option<String> },
+    Id { user: Option<String> },
+    Ftw { path: String, flags: ftw::Flags },
+    Stat { paths: Vec<String> },
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Database {
+    Passwd,
+    Group,
+    Shadow,
+}
+
+fn usage<T>(msg: impl AsRef<str>) -> Result<T, CliError> {
+    Err(CliError::Usage(msg.as_ref().to_owned()))
+}
+
+fn is_help(arg: &str) -> bool {
+    arg == "-h" || arg == "--help"
+}
+
+fn parse_ps(args: &[String]) -> Result<Command, CliError> {
+    let mut user = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("-u", Some(x)) => user = Some(x.to_owned()),
+            ("-u", None) => return usage("ps: option -u requires a value"),
+            (x, _) => return usage(format!("ps: unexpected argument `{}`", x)),
+        }
+    }
+    Ok(Command::Ps { user })
+}
+
+fn parse_pstree(args: &[String]) -> Result<Command, CliError> {
+    let mut pid = None;
+    let mut compact = false;
+    let mut users = false;
+    for arg in args {
+        match arg.as_str() {
+            "-c" => compact = true,
+            "-u" => users = true,
+            x if pid.is_none() => match x.parse() {
+                Ok(x) => pid = Some(x),
+                Err(_) => return usage(format!("pstree: invalid pid `{}`", x)),
+            },
+            x => return usage(format!("pstree: unexpected argument `{}`", x)),
+        }
+    }
+    Ok(Command::Pstree { pid, compact, users })
+}
+
+fn parse_top(args: &[String]) -> Result<Command, CliError> {
+    let (mut count, mut delay) = (10, 1.0);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter.next();
+        match (arg.as_str(), value) {
+            ("-n", Some(x)) => count = x.parse().or_else(|_| usage(format!("top: invalid number `{}`", x)))?,
+            ("-d", Some(x)) => match x.parse::<f64>() {
+                Ok(x) if x > 0.0 && x.is_finite() => delay = x,
+                _ => return usage(format!("top: invalid delay `{}`", x)),
+            },
+            ("-n", None) | ("-d", None) => return usage(format!("top: option {} requires a value", arg)),
+            (x, _) => return usage(format!("top: unexpected argument `{}`", x)),
+        }
+    }
+    Ok(Command::Top { count, delay })
+}
+
+fn parse_ftw(args: &[String]) -> Result<Command, CliError> {
+    let mut flags = ftw::Flags::empty();
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "-d" => flags = flags | ftw::Flags::DEPTH,
+            "-p" => flags = flags | ftw::Flags::PHYS,
+            "-m" => flags = flags | ftw::Flags::MOUNT,
+            x if x.starts_with('-') => return usage(format!("ftw: unknown option `{}`", x)),
+            x if path.is_none() => path = Some(x.to_owned()),
+            x => return usage(format!("ftw: unexpected argument `{}`", x)),
+        }
+    }
+
+    match path {
+        Some(path) => Ok(Command::Ftw { path, flags }),
+        None => usage("ftw: PATH is required"),
+    }
+}
+
+fn parse_getent(args: &[String]) -> Result<Command, CliError> {
+    let (database, rest) = match args.split_first() {
+        Some((database, rest)) => (database, rest),
+        None => return usage("getent: database is required"),
+    };
+    let database = match database.as_str() {
+        "passwd" => Database::Passwd,
+        "group" => Database::Group,
+        "shadow" => Database::Shadow,
+        x => return usage(format!("getent: unknown database `{}`", x)),
+    };
+    match rest {
+        [] => Ok(Command::Getent { database, key: None }),
+        [key] => Ok(Command::Getent { database, key: Some(key.to_owned()) }),
+        _ => usage("getent: too many arguments"),
+    }
+}
+
+fn parse(args: &[String]) -> Result<Command, CliError> {
+    let (name, rest) = match args.split_first() {
+        Some((name, rest)) => (name.as_str(), rest),
+        None => return usage("command is required"),
+    };
+
+    if rest.iter().any(|x| is_help(x)) {
+        return Ok(Command::Help(Some(name.to_owned())));
+    }
+
+    match name {
+        x if is_help(x) => Ok(Command::Help(None)),
+        "help" => match rest {
+            [] => Ok(Command::Help(None)),
+            [command] => Ok(Command::Help(Some(command.to_owned()))),
+            _ => usage("help: too many arguments"),
+        },
+        "ps" => parse_ps(rest),
+        "pstree" => parse_pstree(rest),
+        "fuser" => match rest {
+            [option, path] if option == "-m" => Ok(Command::Fuser { path: path.to_owned(), mount: true }),
+            [path] if !path.starts_with('-') => Ok(Command::Fuser { path: path.to_owned(), mount: false }),
+            _ => usage("fuser: exactly one PATH is expected"),
+        },
+        "lsof" => match rest {
+            [] => Ok(Command::Lsof { pid: None }),
+            [pid] => pid.parse().map(|x| Command::Lsof { pid: Some(x) })
+                .or_else(|_| usage(format!("lsof: invalid pid `{}`", pid))),
+            _ => usage("lsof: too many arguments"),
+        },
+        "netstat" if rest.is_empty() => Ok(Command::Netstat),
+        "netstat" => usage("netstat: no arguments expected"),
+        "top" => parse_top(rest),
+        "getent" => parse_getent(rest),
+        "id" => match rest {
+            [] => Ok(Command::Id { user: None }),
+            [user] => Ok(Command::Id { user: Some(user.to_owned()) }),
+            _ => usage("id: too many arguments"),
+        },
+        "ftw" => parse_ftw(rest),
+        "stat" if rest.is_empty() => usage("stat: PATH is required"),
+        "stat" => Ok(Command::Stat { paths: rest.to_vec() }),
+        x => usage(format!("unknown command `{}`", x)),
+    }
+}
+
+fn help(command: Option<String>) -> CliResult {
+    let text = match command.as_deref() {
+        None => USAGE,
+        Some("ps") => PS_USAGE,
+        Some("pstree") => PSTREE_USAGE,
+        Some("fuser") => FUSER_USAGE,
+        Some("lsof") => LSOF_USAGE,
+        Some("netstat") => NETSTAT_USAGE,
+        Some("top") => TOP_USAGE,
+        Some("getent") => GETENT_USAGE,
+        Some("id") => ID_USAGE,
+        Some("ftw") => FTW_USAGE,
+        Some("stat") => STAT_USAGE,
+        Some(x) => return usage(format!("unknown command `{}`", x)),
+    };
+    println!("{}", text);
+    Ok(())
+}
+
+fn proc_failure(command: &str, e: process::ProcError) -> CliError {
+    CliError::Failure(format!("{}: {}", command, e))
+}
+
+fn ps(user: Option<String>, names: &user::Names) -> CliResult {
+    let failure = |e| proc_failure("ps", e);
+    let processes: Box<dyn Iterator<Item=Result<Process, process::ProcError>>> = match user {
+        Some(user) => {
+            let uid = names.uid(&user).ok_or_else(|| CliError::Failure(format!("ps: user `{}` is not found", user)))?;
+            Box::new(process::get_processes(uid).map_err(failure)?)
+        },
+        None => Box::new(process::get_all_processes().map_err(failure)?),
+    };
+
+    println!("{:>7} {:>7} {:<12} NAME", "PID", "PPID", "USER");
+    for p in processes {
+        let p = p.map_err(failure)?;
+        println!("{:>7} {:>7} {:<12} {}", p.pid, p.ppid, names.user_or_id(p.uid.effective), p.name);
+    }
+    Ok(())
+}
+
+fn pstree(pid: Option<u32>, compact: bool, users: bool) -> CliResult {
+    let tree = process::ProcessTree::from_procfs(&process::Procfs::default())
+        .map_err(|e| proc_failure("pstree", e))?;
+    let rendered = match users {
+        true => tree.render_with_users(pid, compact, &user::Names::load()),
+        false => tree.render(pid, compact),
+    };
+    match rendered {
+        Some(x) => println!("{}", x),
+        None => return Err(CliError::Failure(format!("pstree: process {} is not found", pid.unwrap_or(0)))),
+    }
+    Ok(())
+}
+
+fn fuser(path: &str, mount: bool) -> CliResult {
+    let holding = match mount {
+        true => process::Holding::mount(path),
+        false => process::Holding::file(path),
+    };
+    let holding = holding.map_err(|e| CliError::Failure(format!("fuser: {}: {}", path, e)))?;
+    let holders = process::Procfs::default().holders(&holding).map_err(|e| proc_failure("fuser", e))?;
+    for (p, files) in holders {
+        for f in files {
+            println!("{:>7} {:<16} {:>4} {}", p.pid, p.name, f.fd, f.target.display());
+        }
+    }
+    Ok(())
+}
+
+fn file_kind_name(kind: &process::FileKind) -> String {
+    match kind {
+        process::FileKind::Regular => "REG".to_owned(),
+        process::FileKind::Directory => "DIR".to_owned(),
+        process::FileKind::CharDevice => "CHR".to_owned(),
+        process::FileKind::BlockDevice => "BLK".to_owned(),
+        process::FileKind::Fifo => "FIFO".to_owned(),
+        process::FileKind::Socket(_) => "SOCK".to_owned(),
+        process::FileKind::Pipe(_) => "PIPE".to_owned(),
+        process::FileKind::AnonInode(x) => format!("a_inode:{}", x),
+        process::FileKind::Unknown => "?".to_owned(),
+    }
+}
+
+fn lsof(pid: Option<u32>) -> CliResult {
+    let failure = |e| proc_failure("lsof", e);
+    let procfs = process::Procfs::default();
+    let processes: Vec<Process> = match pid {
+        Some(pid) => vec![procfs.process(pid).map_err(failure)?],
+        None => procfs.processes().map_err(failure)?.collect::<Result<_, _>>().map_err(failure)?,
+    };
+
+    println!("{:>7} {:<16} {:>4} {:<8} {:>10} NAME", "PID", "COMMAND", "FD", "TYPE", "OFFSET");
+    for p in processes {
+        let files = match p.opened_files() {
+            Ok(x) => x,
+            // descriptors of processes of other users are only listed for the explicit pid.
+            Err(e) if pid.is_some() => return Err(failure(e)),
+            Err(_) => continue,
+        };
+        for f in files {
+            let deleted = if f.deleted { " (deleted)" } else { "" };
+            println!(
+                "{:>7} {:<16} {:>4} {:<8} {:>10} {}{}",
+                p.pid, p.name, f.fd, file_kind_name(&f.kind), f.pos, f.target.display(), deleted,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn netstat() -> CliResult {
+    let mut connections = process::Procfs::default().connections().map_err(|e| proc_failure("netstat", e))?;
+    connections.sort_by_key(|x| (x.pid, x.fd));
+    println!("{:<5} {:<40} {:<40} {:<13} PID/NAME", "PROTO", "LOCAL", "REMOTE", "STATE");
+    for c in connections {
+        let remote = c.socket.remote.as_ref().map_or("*".to_owned(), |x| x.to_string());
+        let state = format!("{:?}", c.socket.state);
+        println!(
+            "{:<5} {:<40} {:<40} {:<13} {}/{}",
+            c.socket.protocol.to_string(), c.socket.local.to_string(), remote, state, c.pid, c.name,
+        );
+    }
+    Ok(())
+}
+
+fn top(count: usize, delay: f64) -> CliResult {
+    let failure = |e| proc_failure("top", e);
+    let mut sampler = process::ProcessSampler::new(process::Procfs::default()).map_err(failure)?;
+    sampler.sample().map_err(failure)?;
+    std::thread::sleep(std::time::Duration::from_secs_f64(delay));
+    let samples = sampler.sample().map_err(failure)?;
+
+    let rate = |x: Option<f64>| x.map_or("-".to_owned(), |x| format!("{:.0}", x));
+    println!("{:>7} {:<16} {:>6} {:>6} {:>10} {:>10} {:>8}", "PID", "NAME", "%CPU", "%MEM", "READ/s", "WRITE/s", "CSW/s");
+    for x in process::ProcessSampler::top(samples, count, |x| x.cpu) {
+        println!(
+            "{:>7} {:<16} {:>6.1} {:>6.1} {:>10} {:>10} {:>8.1}",
+            x.pid, x.name, x.cpu, x.memory, rate(x.read_rate), rate(x.write_rate), x.switch_rate,
+        );
+    }
+    Ok(())
+}
+
+fn print_user(u: &user::UserData) {
+    println!("{}", u);
+}
+
+fn print_group(g: &user::GroupData) {
+    println!("{}", g);
+}
+
+fn print_shadow(s: &user::ShadowData) {
+    let days = |x: Option<i64>| x.map(|x| x.to_string()).unwrap_or_default();
+    println!(
+        "{}:{}:{}:{}:{}:{}:{}:{}:",
+        s.name, s.passwd, days(s.last_change), days(s.min), days(s.max), days(s.warn), days(s.inactive), days(s.expire),
+    );
+}
+
+fn passwd_failure(command: &str, e: user::PasswdError, key: &str) -> CliError {
+    match e {
+        user::PasswdError::DoesNotExist => CliError::Failure(format!("{}: `{}` is not found", command, key)),
+        e => CliError::Failure(format!("{}: {}", command, e)),
+    }
+}
+
+/// Prints every entry of a database or the one found by `key`.
+fn print_entries<T>(
+    entries: impl Iterator<Item=Result<T, user::PasswdError>>,
+    key: Option<String>,
+    find: impl Fn(&str) -> Result<T, user::PasswdError>,
+    print: fn(&T),
+) -> CliResult {
+    let key = match key {
+        Some(x) => x,
+        None => {
+            for entry in entries {
+                print(&entry.map_err(|e| passwd_failure("getent", e, ""))?);
+            }
+            return Ok(());
+        },
+    };
+    print(&find(&key).map_err(|e| passwd_failure("getent", e, &key))?);
+    Ok(())
+}
+
+fn getpwkey(key: &str) -> Result<user::UserData, user::PasswdError> {
+    match key.parse() {
+        Ok(uid) => user::getpwuid(uid),
+        Err(_) => user::getpwnam(key),
+    }
+}
+
+fn getent(database: Database, key: Option<String>) -> CliResult {
+    match database {
+        Database::Passwd => print_entries(user::Passwd::new(), key, getpwkey, print_user),
+        Database::Group => print_entries(user::Group::new(), key, |key| match key.parse() {
+            Ok(gid) => user::getgrgid(gid),
+            Err(_) => user::getgrnam(key),
+        }, print_group),
+        Database::Shadow => print_entries(user::Shadow::new(), key, user::getspnam, print_shadow),
+    }
+}
+
+fn id(name: Option<String>) -> CliResult {
+    let found = match &name {
+        Some(x) => getpwkey(x),
+        None => user::getpwuid(unsafe { libc::getuid() }),
+    };
+    let key = name.unwrap_or_default();
+    let found = found.map_err(|e| passwd_failure("id", e, &key))?;
+    let output = user::id(&found, &mut user::Group::new()).map_err(|e| passwd_failure("id", e, &key))?;
+    println!("{}", output);
+    Ok(())
+}
+
+fn type_flag_name(type_flag: &ftw::TypeFlag) -> &'static str {
+    match type_flag {
+        ftw::TypeFlag::File => "f",
+        ftw::TypeFlag::Directory => "d",
+        ftw::TypeFlag::UnreadableDirectory => "dnr",
+        ftw::TypeFlag::PostReadableDirectory => "dp",
+        ftw::TypeFlag::MetadataFailed => "ns",
+        ftw::TypeFlag::Symlink => "sl",
+        ftw::TypeFlag::SymlinkAbsentFile => "sln",
+        ftw::TypeFlag::DirectoryCycle => "dc",
+    }
+}
+
+fn print_ftw_entry(
+    path: &path::Path,
+    _: &io::Result<fs::Metadata>,
+    type_flag: &ftw::TypeFlag,
+    info: &ftw::Ftw,
+) -> ftw::NftwResult {
+    println!("{:<3} {:>3} {}", type_flag_name(type_flag), info.level, path.display());
+    Ok(ftw::CallbackResult::Continue)
+}
+
+fn walk(path: &str, flags: ftw::Flags) -> CliResult {
+    let walker = ftw::Walker::new().nopenfd(FTW_NOPENFD).flags(flags);
+    match walker.walk(path, print_ftw_entry) {
+        Ok(_) | Err(ftw::NftwErr::Stop) => Ok(()),
+        Err(ftw::NftwErr::IO(e)) => Err(CliError::Failure(format!("ftw: {}: {}", path, e))),
+    }
+}
+
+fn file_type(meta: &fs::Metadata) -> &'static str {
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symbolic link"
+    } else if file_type.is_file() {
+        "regular file"
+    } else {
+        "special file"
+    }
+}
+
+fn stat(paths: Vec<String>) -> CliResult {
+    let names = user::Names::load();
+    let mut failed = Vec::new();
+    for path in paths {
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("stat: {}: {}", path, e);
+                failed.push(path);
+                continue;
+            },
+        };
+
+        println!("  File: {}", path);
+        println!("  Size: {:<10} Blocks: {:<10} IO Block: {:<6} {}",
+            meta.size(), meta.blocks(), meta.blksize(), file_type(&meta));
+        println!("Device: {:<10} Inode: {:<11} Links: {}", meta.dev(), meta.ino(), meta.nlink());
+        let uid = format!("({}/{})", meta.uid(), names.user_or_id(meta.uid()));
+        let gid = format!("({}/{})", meta.gid(), names.group_or_id(meta.gid()));
+        println!("Access: ({:04o})     Uid: {:<16} Gid: {}", meta.mode() & 0o7777, uid, gid);
+        println!("Access: {}", meta.atime());
+        println!("Modify: {}", meta.mtime());
+        println!("Change: {}", meta.ctime());
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::Failure(format!("stat: fail reading {}", failed.join(", "))))
+    }
+}
+
+/// Runs a command described by `args`. The first item is a name of the command, the rest are its
+/// arguments.
+pub fn run(args: impl Iterator<Item=String>) -> CliResult {
+    let args: Vec<String> = args.collect();
+    match parse(&args)? {
+        Command::Help(command) => help(command),
+        Command::Ps { user } => ps(user, &user::Names::load()),
+        Command::Pstree { pid, compact, users } => pstree(pid, compact, users),
+        Command::Fuser { path, mount } => fuser(&path, mount),
+        Command::Lsof { pid } => lsof(pid),
+        Command::Netstat => netstat(),
+        Command::Top { count, delay } => top(count, delay),
+        Command::Getent { database, key } => getent(database, key),
+        Command::Id { user } => id(user),
+        Command::Ftw { path, flags } => walk(&path, flags),
+        Command::Stat { paths } => stat(paths),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Command, CliError> {
+        let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+        parse(&args)
+    }
+
+    fn is_usage_error(res: Result<Command, CliError>) -> bool {
+        matches!(res, Err(CliError::Usage(_)))
+    }
+
+    #[test]
+    fn no_command() {
+        let res = parse_str(&[]);
+        assert!(is_usage_error(res));
+    }
+
+    #[test]
+    fn unknown_command() {
+        assert!(is_usage_error(parse_str(&["htop"])));
+        assert_eq!(CliError::Usage("".to_owned()).code(), 2);
+        assert_eq!(CliError::Failure("".to_owned()).code(), 1);
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(parse_str(&["--help"]), Ok(Command::Help(None)));
+        assert_eq!(parse_str(&["help"]), Ok(Command::Help(None)));
+        assert_eq!(parse_str(&["help", "ps"]), Ok(Command::Help(Some("ps".to_owned()))));
+        assert_eq!(parse_str(&["ftw", "-h"]), Ok(Command::Help(Some("ftw".to_owned()))));
+    }
+
+    #[test]
+    fn ps() {
+        assert_eq!(parse_str(&["ps"]), Ok(Command::Ps { user: None }));
+        assert_eq!(parse_str(&["ps", "-u", "1000"]), Ok(Command::Ps { user: Some("1000".to_owned()) }));
+        assert_eq!(parse_str(&["ps", "-u", "root"]), Ok(Command::Ps { user: Some("root".to_owned()) }));
+        assert!(is_usage_error(parse_str(&["ps", "-u"])));
+        assert!(is_usage_error(parse_str(&["ps", "root"])));
+    }
+
+    #[test]
+    fn ps_with_broken_passwd() {
+        let passwd = std::env::temp_dir().join(format!("cli_ps_passwd_{}", std::process::id()));
+        fs::write(&passwd, "+::::::\nbroken\nbob:x:1002:100::/:/bin/sh\n").unwrap();
+        let group = user::Group::with_path("/nonexistent/group");
+        let names = user::Names::from_databases(user::Passwd::with_path(&passwd), group);
+        fs::remove_file(&passwd).unwrap();
+
+        assert_eq!(super::ps(None, &names), Ok(()));
+        assert_eq!(super::ps(Some("bob".to_owned()), &names), Ok(()));
+        assert!(matches!(super::ps(Some("alice".to_owned()), &names), Err(CliError::Failure(_))));
+    }
+
+    #[test]
+    fn pstree() {
+        assert_eq!(parse_str(&["pstree"]), Ok(Command::Pstree { pid: None, compact: false, users: false }));
+        assert_eq!(parse_str(&["pstree", "-c", "1"]), Ok(Command::Pstree { pid: Some(1), compact: true, users: false }));
+        assert_eq!(parse_str(&["pstree", "-u", "-c"]), Ok(Command::Pstree { pid: None, compact: true, users: true }));
+        assert!(is_usage_error(parse_str(&["pstree", "init"])));
+        assert!(is_usage_error(parse_str(&["pstree", "1", "2"])));
+    }
+
+    #[test]
+    fn fuser_and_lsof() {
+        assert_eq!(parse_str(&["fuser", "/tmp"]), Ok(Command::Fuser { path: "/tmp".to_owned(), mount: false }));
+        assert_eq!(parse_str(&["fuser", "-m", "/"]), Ok(Command::Fuser { path: "/".to_owned(), mount: true }));
+        assert!(is_usage_error(parse_str(&["fuser", "-x"])));
+        assert!(is_usage_error(parse_str(&["fuser"])));
+        assert_eq!(parse_str(&["lsof"]), Ok(Command::Lsof { pid: None }));
+        assert_eq!(parse_str(&["lsof", "1"]), Ok(Command::Lsof { pid: Some(1) }));
+        assert!(is_usage_error(parse_str(&["lsof", "init"])));
+        assert_eq!(parse_str(&["netstat"]), Ok(Command::Netstat));
+        assert!(is_usage_error(parse_str(&["netstat", "-p"])));
+    }
+
+    #[test]
+    fn top() {
+        assert_eq!(parse_str(&["top"]), Ok(Command::Top { count: 10, delay: 1.0 }));
+        assert_eq!(parse_str(&["top", "-d", "0.5", "-n", "3"]), Ok(Command::Top { count: 3, delay: 0.5 }));
+        assert!(is_usage_error(parse_str(&["top", "-n"])));
+        assert!(is_usage_error(parse_str(&["top", "-d", "0"])));
+        assert!(is_usage_error(parse_str(&["top", "-x", "1"])));
+    }
+
+    #[test]
+    fn getent() {
+        assert_eq!(parse_str(&["getent", "passwd"]), Ok(Command::Getent { database: Database::Passwd, key: None }));
+        assert_eq!(
+            parse_str(&["getent", "group", "root"]),
+            Ok(Command::Getent { database: Database::Group, key: Some("root".to_owned()) })
+        );
+        assert_eq!(parse_str(&["getent", "shadow"]), Ok(Command::Getent { database: Database::Shadow, key: None }));
+        assert!(is_usage_error(parse_str(&["getent", "hosts", "localhost"])));
+        assert!(is_usage_error(parse_str(&["getent", "passwd", "root", "daemon"])));
+        assert!(is_usage_error(parse_str(&["getent"])));
+        assert_eq!(parse_str(&["id"]), Ok(Command::Id { user: None }));
+        assert_eq!(parse_str(&["id", "root"]), Ok(Command::Id { user: Some("root".to_owned()) }));
+        assert!(is_usage_error(parse_str(&["id", "root", "daemon"])));
+    }
+
+    #[test]
+    fn ftw() {
+        assert_eq!(parse_str(&["ftw", "/tmp"]), Ok(Command::Ftw { path: "/tmp".to_owned(), flags: ftw::Flags::empty() }));
+        assert_eq!(
+            parse_str(&["ftw", "-d", "/tmp", "-p", "-m"]),
+            Ok(Command::Ftw { path: "/tmp".to_owned(), flags: ftw::Flags::DEPTH | ftw::Flags::PHYS | ftw::Flags::MOUNT })
+        );
+        assert!(is_usage_error(parse_str(&["ftw"])));
+        assert!(is_usage_error(parse_str(&["ftw", "-x", "/tmp"])));
+    }
+
+    #[test]
+    fn stat() {
+        assert_eq!(parse_str(&["stat", "a", "b"]), Ok(Command::Stat { paths: vec!["a".to_owned(), "b".to_owned()] }));
+        assert!(is_usage_error(parse_str(&["stat"])));
+    }
+}
@@ -0,0 +1,110 @@
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::RawFd;
+use std::ptr::NonNull;
+
+fn cvt(res: libc::c_int) -> io::Result<libc::c_int> {
+    if res == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(res)
+    }
+}
+
+/// Owned file descriptor closed on drop.
+pub struct Fd(RawFd);
+
+impl Fd {
+    pub fn openat(dirfd: RawFd, name: &CStr, flags: libc::c_int) -> io::Result<Self> {
+        let fd = cvt(unsafe { libc::openat(dirfd, name.as_ptr(), flags | libc::O_CLOEXEC) })?;
+        Ok(Fd(fd))
+    }
+
+    /// Opens a file without following symbolic links when `follow` is false. The descriptor is
+    /// good only for getting metadata and as a `dirfd` of the other calls.
+    pub fn open_path(dirfd: RawFd, name: &CStr, follow: bool) -> io::Result<Self> {
+        let nofollow = if follow { 0 } else { libc::O_NOFOLLOW };
+        Self::openat(dirfd, name, libc::O_PATH | nofollow)
+    }
+
+    pub fn raw(&self) -> RawFd {
+        self.0
+    }
+
+    /// `fs::Metadata` can't be built from `libc::stat` so std does fstat on the borrowed descriptor.
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        let file = ManuallyDrop::new(unsafe { fs::File::from_raw_fd(self.0) });
+        file.metadata()
+    }
+
+    pub fn fchdir(&self) -> io::Result<()> {
+        fchdir(self.0)
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+pub fn fchdir(fd: RawFd) -> io::Result<()> {
+    cvt(unsafe { libc::fchdir(fd) }).map(|_| ())
+}
+
+/// Directory stream. Holds exactly one descriptor.
+pub struct Dir(NonNull<libc::DIR>);
+
+// the stream is used by one thread at a time, it's only passed to another one.
+unsafe impl Send for Dir {}
+
+impl Dir {
+    /// Opens a directory `at` points to. `at` is usually opened by `Fd::open_path` so the
+    /// directory is the same file metadata was read from.
+    pub fn open(at: &Fd) -> io::Result<Self> {
+        let dot = CString::new(".").unwrap();
+        let fd = Fd::openat(at.raw(), &dot, libc::O_RDONLY | libc::O_DIRECTORY)?;
+        let stream = NonNull::new(unsafe { libc::fdopendir(fd.raw()) }).ok_or_else(io::Error::last_os_error)?;
+        // the stream owns the descriptor from now on.
+        std::mem::forget(fd);
+        Ok(Dir(stream))
+    }
+
+    pub fn raw(&self) -> RawFd {
+        unsafe { libc::dirfd(self.0.as_ptr()) }
+    }
+
+    /// Returns a name of the next entry skipping `.` and `..`.
+    pub fn read(&mut self) -> Option<io::Result<CString>> {
+        loop {
+            let entry = unsafe {
+                *libc::__errno_location() = 0;
+                libc::readdir(self.0.as_ptr())
+            };
+
+            if entry.is_null() {
+                let e = io::Error::last_os_error();
+                return match e.raw_os_error() {
+                    Some(0) => None,
+                    _ => Some(Err(e)),
+                };
+            }
+
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            match name.to_bytes() {
+                b"." | b".." => continue,
+                _ => return Some(Ok(name.to_owned())),
+            }
+        }
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        unsafe { libc::closedir(self.0.as_ptr()) };
+    }
+}
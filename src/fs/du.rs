@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::path;
+use std::os::unix::fs::MetadataExt;
+
+use super::CallbackResult;
+use super::Flags;
+use super::NftwErr;
+use super::TypeFlag;
+use super::Walker;
+
+/// Space taken by a directory and everything under it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+    pub path: path::PathBuf,
+    /// Number of 512-byte blocks allocated.
+    pub blocks: u64,
+    /// Sum of file sizes.
+    pub bytes: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Total {
+    blocks: u64,
+    bytes: u64,
+}
+
+/// Computes usage of every directory under `root` like du(1). Files with several hard links and
+/// directories met through symbolic links are counted and reported once. Directories come after
+/// their subdirectories, `root` is the last one. `walker` sets how symbolic links and mount points
+/// are treated, `DEPTH` is always set. With `PHYS` a symbolic link is counted by its own blocks
+/// and it's never reported as a directory like `du -P` does.
+pub fn disk_usage<P: AsRef<path::Path>>(walker: &Walker, root: P) -> Result<Vec<Usage>, NftwErr> {
+    let mut usages = vec![];
+    let mut totals: Vec<Total> = vec![]; // content of directories being walked by their level
+    let mut seen = HashSet::new();
+    let is_physical = walker.flags.contains(Flags::PHYS);
+    walker.clone().depth(true).action_retval(false).walk(root, |path, meta, type_flag, ftw| {
+        let meta = match meta {
+            // the directory is one of the directories being counted.
+            Ok(_) if *type_flag == TypeFlag::DirectoryCycle => return Ok(CallbackResult::Continue),
+            Ok(meta) => meta,
+            Err(_) => return Ok(CallbackResult::Continue),
+        };
+        let level = ftw.level as usize;
+        if totals.len() <= level {
+            totals.resize(level + 1, Total::default());
+        }
+
+        let mut total = Total::default();
+        // any entry can be met twice if symbolic links are followed.
+        let is_single = is_physical && meta.nlink() < 2;
+        let is_first = is_single || seen.insert((meta.dev(), meta.ino()));
+        if is_first {
+            total.blocks = meta.blocks();
+            total.bytes = meta.size();
+        }
+
+        if let TypeFlag::PostReadableDirectory = type_flag {
+            // content of a directory met again is counted already, it's all zeros.
+            if !is_first {
+                totals[level] = Total::default();
+                return Ok(CallbackResult::Continue);
+            }
+
+            let content = std::mem::take(&mut totals[level]);
+            total.blocks += content.blocks;
+            total.bytes += content.bytes;
+            usages.push(Usage { path: path.to_path_buf(), blocks: total.blocks, bytes: total.bytes });
+        }
+
+        if level > 0 {
+            totals[level - 1].blocks += total.blocks;
+            totals[level - 1].bytes += total.bytes;
+        }
+        Ok(CallbackResult::Continue)
+    })?;
+    Ok(usages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use super::super::tests::Fixture;
+
+    #[test]
+    fn disk_usage_of_tree() {
+        let fixture = Fixture::new("disk_usage_of_tree");
+        fs::write(fixture.root.join("d").join("e").join("big"), vec![0u8; 10000]).unwrap();
+        fs::hard_link(fixture.root.join("d").join("e").join("big"), fixture.root.join("big_link")).unwrap();
+        let size_of = |name: &str| fs::symlink_metadata(fixture.root.join(name)).unwrap().size();
+
+        // `big_link` is met before `d/e/big` so the file is counted in the root only.
+        let usages = disk_usage(&Walker::new().physical(true).sorted(true), &fixture.root).unwrap();
+        let paths: Vec<_> = usages.iter().map(|x| x.path.strip_prefix(&fixture.root).unwrap().to_owned()).collect();
+        assert_eq!(paths, vec![path::Path::new("d/e"), path::Path::new("d"), path::Path::new("")]);
+
+        let e = size_of("d/e");
+        assert_eq!(usages[0].bytes, e);
+        let d = size_of("d") + size_of("d/b") + e;
+        assert_eq!(usages[1].bytes, d);
+        let root = size_of("") + size_of("a") + 10000 + size_of("link_d") + size_of("dangling") + d;
+        assert_eq!(usages[2].bytes, root);
+        assert!(usages[2].blocks >= usages[1].blocks + usages[0].blocks);
+
+        // the link is counted by its own size and it isn't reported as a directory.
+        assert!(usages.iter().all(|x| x.path != fixture.root.join("link_d")));
+
+        // `d` is met through `link_d` as well but it's counted and reported once.
+        let usages = disk_usage(&Walker::new().sorted(true), &fixture.root).unwrap();
+        assert_eq!(usages.last().unwrap().bytes, root - size_of("link_d"));
+        let paths: Vec<_> = usages.iter().map(|x| x.path.strip_prefix(&fixture.root).unwrap().to_owned()).collect();
+        assert_eq!(paths, vec![path::Path::new("d/e"), path::Path::new("d"), path::Path::new("")]);
+        assert_eq!(usages[1].bytes, d);
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::ops::Not;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path;
+use std::time;
+
+use crate::user::Names;
+
+use super::CallbackResult;
+use super::NftwErr;
+use super::Walker;
+
+/// Expression over an entry of a file tree like an expression of find(1).
+///
+/// # Examples
+///
+/// ```ignore
+/// let week_ago = time::SystemTime::now() - time::Duration::from_secs(7 * 24 * 3600);
+/// let filter = Filter::Name("*.rs".to_owned())
+///     .and(Filter::Modified(week_ago, time::SystemTime::now()))
+///     .and(!Filter::Uid(0));
+/// let found = find(&Walker::new(), "src", &filter);
+/// ```
+#[derive(Clone, Debug)]
+pub enum Filter {
+    /// The file name matches a glob pattern supporting `*`, `?` and `[...]`.
+    Name(String),
+    /// Modification time is in the range, the end is excluded.
+    Modified(time::SystemTime, time::SystemTime),
+    /// Size in bytes is in the range, both bounds are included.
+    Size(u64, u64),
+    Uid(u32),
+    Gid(u32),
+    /// All bits of the mask are set in the mode.
+    Perm(u32),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Files owned by a user given by a name or a uid like `find -user`. `None` if there is no
+    /// such user.
+    pub fn user(user: &str, names: &Names) -> Option<Filter> {
+        names.uid(user).map(Filter::Uid)
+    }
+
+    /// Files owned by a group given by a name or a gid like `find -group`.
+    pub fn group(group: &str, names: &Names) -> Option<Filter> {
+        names.gid(group).map(Filter::Gid)
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches(&self, path: &path::Path, meta: &fs::Metadata) -> bool {
+        match self {
+            Filter::Name(pattern) => {
+                let name = path.file_name().map_or(path.as_os_str().as_bytes(), |x| x.as_bytes());
+                glob_match(pattern.as_bytes(), name)
+            },
+            Filter::Modified(from, to) => meta.modified().is_ok_and(|x| *from <= x && x < *to),
+            Filter::Size(min, max) => *min <= meta.size() && meta.size() <= *max,
+            Filter::Uid(uid) => meta.uid() == *uid,
+            Filter::Gid(gid) => meta.gid() == *gid,
+            Filter::Perm(mask) => meta.mode() & mask == *mask,
+            Filter::And(left, right) => left.matches(path, meta) && right.matches(path, meta),
+            Filter::Or(left, right) => left.matches(path, meta) || right.matches(path, meta),
+            Filter::Not(filter) => !filter.matches(path, meta),
+        }
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+/// Matches `name` against shell wildcards. A class `[...]` supports ranges and negation by `!`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    // position of the last `*` and the position in `name` it matches up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            },
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match match_class(&pattern[p ..], name[n]) {
+                Some(matched) => matched.map(|len| p + len),
+                // an unclosed bracket is an ordinary character.
+                None if name[n] == b'[' => Some(p + 1),
+                None => None,
+            },
+            Some(x) if *x == name[n] => Some(p + 1),
+            _ => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            },
+            (None, Some((star, matched))) => {
+                backtrack = Some((star, matched + 1));
+                p = star + 1;
+                n = matched + 1;
+            },
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p ..].iter().all(|x| *x == b'*')
+}
+
+/// Matches `c` against a class at the beginning of `pattern`. Returns `None` if the class isn't
+/// closed, otherwise the length of the class if `c` matches it.
+fn match_class(pattern: &[u8], c: u8) -> Option<Option<usize>> {
+    let negate = pattern.get(1) == Some(&b'!');
+    let start = if negate { 2 } else { 1 };
+    // `]` right after the opening is a member of the class.
+    let end = pattern.iter().skip(start + 1).position(|x| *x == b']')? + start + 1;
+    let members = &pattern[start .. end];
+    let mut is_member = false;
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == b'-' {
+            is_member |= members[i] <= c && c <= members[i + 2];
+            i += 3;
+        } else {
+            is_member |= members[i] == c;
+            i += 1;
+        }
+    }
+
+    Some(if is_member != negate { Some(end + 1) } else { None })
+}
+
+/// Collects paths of entries under `root` matching `filter`. Entries which metadata can't be read
+/// never match.
+pub fn find<P: AsRef<path::Path>>(walker: &Walker, root: P, filter: &Filter) -> Result<Vec<path::PathBuf>, NftwErr> {
+    let mut found = vec![];
+    walker.walk(root, |path, meta, _, _| {
+        if meta.as_ref().is_ok_and(|x| filter.matches(path, x)) {
+            found.push(path.to_path_buf());
+        }
+        Ok(CallbackResult::Continue)
+    })?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::Fixture;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match(b"*.rs", b"mod.rs"));
+        assert!(glob_match(b"*.rs", b".rs"));
+        assert!(!glob_match(b"*.rs", b"mod.rsx"));
+        assert!(glob_match(b"m?d.*", b"mod.rs"));
+        assert!(glob_match(b"*", b""));
+        assert!(!glob_match(b"?", b""));
+        assert!(glob_match(b"a*b*c", b"aXbYbZc"));
+        assert!(!glob_match(b"a*b*c", b"aXbYbZ"));
+        assert!(glob_match(b"[a-c]x", b"bx"));
+        assert!(!glob_match(b"[a-c]x", b"dx"));
+        assert!(glob_match(b"[!a-c]x", b"dx"));
+        assert!(glob_match(b"[]]", b"]"));
+        assert!(glob_match(b"[ab", b"[ab"));
+        assert!(glob_match(b"*[0-9]", b"file7"));
+    }
+
+    #[test]
+    fn owners() {
+        let names = Names::load();
+        let meta = fs::metadata("/").unwrap();
+        let root = path::Path::new("/");
+        assert!(Filter::user("root", &names).unwrap().matches(root, &meta));
+        assert!(Filter::group("0", &names).unwrap().matches(root, &meta));
+        assert!(!Filter::user("1", &names).unwrap().matches(root, &meta));
+        assert!(Filter::user("no such user", &names).is_none());
+    }
+
+    #[test]
+    fn find_by_filter() {
+        let fixture = Fixture::new("find_by_filter");
+        let relative = |found: Vec<path::PathBuf>| -> Vec<String> {
+            let mut found: Vec<_> = found.iter()
+                .map(|x| x.strip_prefix(&fixture.root).unwrap().to_str().unwrap().to_owned()).collect();
+            found.sort();
+            found
+        };
+        let walker = Walker::new().physical(true);
+
+        let found = find(&walker, &fixture.root, &Filter::Name("[ab]".to_owned())).unwrap();
+        assert_eq!(relative(found), vec!["a", "d/b"]);
+
+        let filter = Filter::Size(1, 1).and(!Filter::Name("[al]*".to_owned()));
+        assert_eq!(relative(find(&walker, &fixture.root, &filter).unwrap()), vec!["d/b"]);
+
+        let uid = fs::metadata(&fixture.root).unwrap().uid();
+        let filter = Filter::Perm(0o040000).and(Filter::Uid(uid)).or(Filter::Name("*ing".to_owned()));
+        assert_eq!(relative(find(&walker, &fixture.root, &filter).unwrap()), vec!["", "d", "d/e", "dangling"]);
+
+        let now = std::time::SystemTime::now();
+        let hour = std::time::Duration::from_secs(3600);
+        let filter = Filter::Modified(now - hour, now + hour).and(Filter::Gid(fs::metadata("/").unwrap().gid() + 1));
+        assert_eq!(find(&walker, &fixture.root, &!filter).unwrap().len(), 7);
+    }
+}
@@ -1,31 +1,78 @@
-use libc;
-
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
 use std::fs;
 use std::path;
-use std::mem;
-use std::ffi::CString;
-use std::error::Error;
 use std::io;
+use std::ops::BitOr;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::MetadataExt;
-
-static FTW_ACTIONRETVAL: Flag = 1;
-static FTW_CHDIR: Flag = 2;
-static FTW_DEPTH: Flag = 4;
-static FTW_MOUNT: Flag = 8;
-static FTW_PHYS: Flag = 16;
+use std::os::unix::io::RawFd;
+
+mod dir;
+mod du;
+mod find;
+mod parallel;
+use self::dir::Dir;
+use self::dir::Fd;
+pub use self::du::disk_usage;
+pub use self::du::Usage;
+pub use self::find::find;
+pub use self::find::Filter;
 
 const DEFALT_NFTW: NftwResult = Ok(CallbackResult::Continue);
+const DEFAULT_NOPENFD: usize = 20;
+
+pub type NftwResult = Result<CallbackResult, NftwErr>;
+type Func<'a> = dyn FnMut(&path::Path, &io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult + 'a;
+
+/// Options of a walk, named after `FTW_*` flags of nftw(3). Combined by `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// Results of the callback other than `Continue` control the walk instead of stopping it.
+    pub const ACTIONRETVAL: Flags = Flags(1);
+    /// The working directory is changed to a directory before its entries are reported.
+    pub const CHDIR: Flags = Flags(2);
+    /// A directory is reported after its content as `PostReadableDirectory`.
+    pub const DEPTH: Flags = Flags(4);
+    /// Entries on file systems other than the one of the root are neither reported nor walked.
+    pub const MOUNT: Flags = Flags(8);
+    /// Symbolic links are reported as `Symlink` instead of being followed.
+    pub const PHYS: Flags = Flags(16);
+
+    pub fn empty() -> Self {
+        Flags(0)
+    }
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    fn set(self, other: Flags, on: bool) -> Self {
+        if on { Flags(self.0 | other.0) } else { Flags(self.0 & !other.0) }
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
 
-type Func = dyn Fn(&io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult;
-type NftwResult = Result<CallbackResult, NftwErr>;
-type Flag = u8;
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
 
-struct Ftw {
-    base: u32,
-    level: u32,
+#[derive(Clone, Debug)]
+pub struct Ftw {
+    /// Offset of the file name in the reported path.
+    pub base: u32,
+    pub level: u32,
 }
 
-enum NftwErr {
+#[derive(Debug)]
+pub enum NftwErr {
     IO(io::Error),
     Stop,
 }
@@ -36,7 +83,8 @@ impl From<io::Error> for NftwErr {
     }
 }
 
-enum TypeFlag {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeFlag {
     File, // FTW_F
     Directory, // FTW_D
     UnreadableDirectory, // FTW_DNR
@@ -44,133 +92,425 @@ enum TypeFlag {
     MetadataFailed, // FTW_NS
     Symlink, // FTW_SL
     SymlinkAbsentFile, // FTW_SLN
+    DirectoryCycle, // FTS_DC, a directory which is an ancestor of itself. It's not walked.
 }
 
-enum CallbackResult {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallbackResult {
     Continue,
     SkipSiblings,
     SkipSubTree,
 }
 
-fn nftw(dirname: &str, action: &Func, nopenfd: i32, flags: Flag) -> NftwResult
-{
-    let mut ftw = Ftw{base: 0, level: 0};
-    _nftw(path::Path::new(dirname), action, nopenfd, flags, &mut ftw)
-}
-
-fn metadata_ok(
-    meta_ok: fs::Metadata,
-    from_path: &path::Path,
-    flags: Flag
-) -> (io::Result<fs::Metadata>, TypeFlag) {
-    let flag = if meta_ok.is_file() {
-        TypeFlag::File
-    } else if meta_ok.is_dir() {
-        match from_path.read_dir() {
-            Ok(_) if flags & FTW_DEPTH != 0 => TypeFlag::PostReadableDirectory,
-            Ok(_) => TypeFlag::Directory,
-            Err(_) => TypeFlag::UnreadableDirectory,
+/// Builder of a file tree walk.
+///
+/// # Examples
+///
+/// ```ignore
+/// let res = Walker::new().depth(true).walk("/tmp", |path, _, type_flag, ftw| {
+///     println!("{:?} {} {}", type_flag, ftw.level, path.display());
+///     Ok(CallbackResult::Continue)
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Walker {
+    flags: Flags,
+    nopenfd: usize,
+    sorted: bool,
+}
+
+impl Default for Walker {
+    fn default() -> Self {
+        Self { flags: Flags::empty(), nopenfd: DEFAULT_NOPENFD, sorted: false }
+    }
+}
+
+impl Walker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn flags(mut self, flags: Flags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Maximum number of directories kept open simultaneously.
+    pub fn nopenfd(mut self, nopenfd: usize) -> Self {
+        self.nopenfd = nopenfd;
+        self
+    }
+
+    /// Entries of a directory are reported in byte order of their names instead of the order
+    /// they are read in. It requires reading a directory before reporting its entries.
+    pub fn sorted(mut self, on: bool) -> Self {
+        self.sorted = on;
+        self
+    }
+
+    pub fn action_retval(mut self, on: bool) -> Self {
+        self.flags = self.flags.set(Flags::ACTIONRETVAL, on);
+        self
+    }
+
+    pub fn chdir(mut self, on: bool) -> Self {
+        self.flags = self.flags.set(Flags::CHDIR, on);
+        self
+    }
+
+    pub fn mount(mut self, on: bool) -> Self {
+        self.flags = self.flags.set(Flags::MOUNT, on);
+        self
+    }
+
+    pub fn depth(mut self, on: bool) -> Self {
+        self.flags = self.flags.set(Flags::DEPTH, on);
+        self
+    }
+
+    pub fn physical(mut self, on: bool) -> Self {
+        self.flags = self.flags.set(Flags::PHYS, on);
+        self
+    }
+
+    /// Calls `action` for every entry of the tree rooted at `dirname` including the root itself.
+    /// Fails if metadata of the root can't be read. The working directory is restored after the
+    /// walk if `CHDIR` is set.
+    pub fn walk<P, F>(&self, dirname: P, mut action: F) -> NftwResult
+    where
+        P: AsRef<path::Path>,
+        F: FnMut(&path::Path, &io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult,
+    {
+        let dirname = dirname.as_ref();
+        let entry = resolve(libc::AT_FDCWD, &to_cstring(dirname)?, self.flags);
+        let dev = match entry.meta {
+            Ok(ref meta) => meta.dev(),
+            // nothing can be said about the root so there is no reason to call the callback.
+            Err(e) => return Err(NftwErr::IO(e)),
+        };
+
+        let absolute_root = absolute(dirname)?;
+
+        let cwd = if self.flags.contains(Flags::CHDIR) {
+            let dot = CString::new(".").unwrap();
+            Some(Fd::openat(libc::AT_FDCWD, &dot, libc::O_RDONLY | libc::O_DIRECTORY)?)
+        } else {
+            None
+        };
+
+        let mut walk = Walk {
+            flags: self.flags,
+            sorted: self.sorted,
+            action: &mut action,
+            ftw: Ftw { base: 0, level: 0 },
+            nopenfd: self.nopenfd,
+            dev,
+            root: dirname.to_path_buf(),
+            absolute_root,
+            frames: vec![],
+            cwd,
+        };
+
+        let res = walk.entry(dirname, entry);
+        if let Some(ref initial) = walk.cwd {
+            initial.fchdir()?;
         }
+        res.and(DEFALT_NFTW)
+    }
+}
+
+pub fn nftw<P, F>(dirname: P, action: F, nopenfd: usize, flags: Flags) -> NftwResult
+where
+    P: AsRef<path::Path>,
+    F: FnMut(&path::Path, &io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult,
+{
+    Walker::new().nopenfd(nopenfd).flags(flags).walk(dirname, action)
+}
+
+/// An entry opened to be reported. `fd` is kept for directories only.
+struct Entry {
+    fd: Option<Fd>,
+    meta: io::Result<fs::Metadata>,
+    type_flag: TypeFlag,
+}
+
+impl Entry {
+    fn failed(e: io::Error) -> Self {
+        Self { fd: None, meta: Err(e), type_flag: TypeFlag::MetadataFailed }
+    }
+}
+
+fn to_cstring(path: &path::Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn absolute(path: &path::Path) -> io::Result<path::PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
     } else {
-        TypeFlag::File
-    };
-    (Ok(meta_ok), flag)
-}
-
-fn metadata_err(
-    from_path: &path::Path,
-    flags: Flag,
-) -> (io::Result<fs::Metadata>, TypeFlag) {
-    let meta = from_path.symlink_metadata();
-    let flag = match meta {
-        Ok(_) if flags & FTW_PHYS != 0 => TypeFlag::Symlink,
-        Ok(_) => TypeFlag::SymlinkAbsentFile,
-        Err(_) => TypeFlag::MetadataFailed,
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+/// Opens a directory by its path again. The path must still lead to the same directory.
+fn reopen(absolute: &path::Path, dev: u64, ino: u64) -> io::Result<Fd> {
+    let fd = Fd::open_path(libc::AT_FDCWD, &to_cstring(absolute)?, true)?;
+    let meta = fd.metadata()?;
+    if (meta.dev(), meta.ino()) != (dev, ino) {
+        return Err(io::Error::other(format!("{} is replaced during the walk", absolute.display())));
+    }
+    Ok(fd)
+}
+
+/// Without `ACTIONRETVAL` any result except `Continue` stops the walk.
+fn normalize(res: CallbackResult, flags: Flags) -> NftwResult {
+    match res {
+        CallbackResult::Continue => Ok(res),
+        _ if flags.contains(Flags::ACTIONRETVAL) => Ok(res),
+        _ => Err(NftwErr::Stop),
+    }
+}
+
+/// Offset of the last component of `path` ignoring trailing slashes.
+fn base_of(path: &path::Path) -> u32 {
+    let bytes = path.as_os_str().as_bytes();
+    let end = match bytes.iter().rposition(|x| *x != b'/') {
+        Some(x) => x + 1,
+        None => return 0,
     };
-    (meta, flag)
+    bytes[.. end].iter().rposition(|x| *x == b'/').map_or(0, |x| x + 1) as u32
 }
 
-fn resolve_meta_and_type_flag(
-    from_path: &path::Path, flags: Flag,
-) -> (io::Result<fs::Metadata>, TypeFlag) {
-    match from_path.metadata() {
-        Ok(x) => metadata_ok(x, from_path, flags),
-        Err(_) => metadata_err(from_path, flags),
+fn resolve(dirfd: RawFd, name: &CStr, flags: Flags) -> Entry {
+    let follow = !flags.contains(Flags::PHYS);
+    let e = match Fd::open_path(dirfd, name, follow) {
+        Ok(fd) => return match fd.metadata() {
+            Ok(meta) if meta.is_dir() => Entry { fd: Some(fd), meta: Ok(meta), type_flag: TypeFlag::Directory },
+            Ok(meta) if meta.file_type().is_symlink() => Entry { fd: None, meta: Ok(meta), type_flag: TypeFlag::Symlink },
+            Ok(meta) => Entry { fd: None, meta: Ok(meta), type_flag: TypeFlag::File },
+            Err(e) => Entry::failed(e),
+        },
+        Err(e) => e,
+    };
+
+    if !follow || e.kind() != io::ErrorKind::NotFound {
+        return Entry::failed(e);
+    }
+
+    // the target of a symbolic link is absent.
+    match Fd::open_path(dirfd, name, false).and_then(|fd| fd.metadata()) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            Entry { fd: None, meta: Ok(meta), type_flag: TypeFlag::SymlinkAbsentFile }
+        },
+        _ => Entry::failed(e),
     }
 }
 
-fn read_dir(from_path: &path::Path, action: &Func, nopenfd: i32, flags: Flag, ftw: &mut Ftw) -> NftwResult {
-    for entry in from_path.read_dir()? {
-        let path = entry?.path().to_owned();
+/// A directory which entries are being reported.
+struct Frame {
+    path: path::PathBuf,
+    dev: u64,
+    ino: u64,
+    stream: Option<Dir>,
+    reopened: Option<Fd>, // the directory opened again after the stream is closed
+    pending: VecDeque<CString>, // entries read ahead to close the stream
+}
+
+impl Frame {
+    fn fd(&self) -> Option<RawFd> {
+        self.stream.as_ref().map(Dir::raw).or_else(|| self.reopened.as_ref().map(Fd::raw))
+    }
 
-        ftw.level += 1;
-        let res = _nftw(&path, action, nopenfd, flags, ftw)?;
-        ftw.level -= 1;
+    fn next_name(&mut self) -> Option<io::Result<CString>> {
+        if let Some(name) = self.pending.pop_front() {
+            return Some(Ok(name));
+        }
+        self.stream.as_mut().and_then(Dir::read)
+    }
 
-        match res {
-            CallbackResult::SkipSiblings => break,
-            _ => continue,
+    /// Reads names of the rest of entries ahead.
+    fn read_ahead(&mut self) -> io::Result<()> {
+        if let Some(ref mut stream) = self.stream {
+            while let Some(name) = stream.read() {
+                self.pending.push_back(name?);
+            }
         }
+        Ok(())
     }
-    DEFALT_NFTW
+
+    /// Releases the descriptor keeping names of entries which are not reported yet.
+    fn close(&mut self) -> io::Result<()> {
+        self.read_ahead()?;
+        self.stream = None;
+        self.reopened = None;
+        Ok(())
+    }
+}
+
+struct Walk<'a, 'f> {
+    flags: Flags,
+    sorted: bool,
+    action: &'a mut Func<'f>,
+    ftw: Ftw,
+    nopenfd: usize,
+    dev: u64, // device of the root
+    root: path::PathBuf,
+    absolute_root: path::PathBuf, // to reopen directories closed to fit `nopenfd`
+    frames: Vec<Frame>,
+    cwd: Option<Fd>, // the initial working directory if `CHDIR` is set
 }
 
-fn normalize(res: CallbackResult, flags: Flag) -> NftwResult {
-    if flags | FTW_ACTIONRETVAL == 0 {
-        match res {
-            CallbackResult::Continue => Ok(res),
-            _ => Err(NftwErr::Stop),
+impl<'a, 'f> Walk<'a, 'f> {
+    fn call(&mut self, path: &path::Path, meta: &io::Result<fs::Metadata>, type_flag: &TypeFlag) -> NftwResult {
+        self.ftw.base = base_of(path);
+        let res = (self.action)(path, meta, type_flag, &self.ftw)?;
+        self.normalize(res)
+    }
+
+    fn normalize(&self, res: CallbackResult) -> NftwResult {
+        normalize(res, self.flags)
+    }
+
+    fn entry(&mut self, path: &path::Path, entry: Entry) -> NftwResult {
+        match entry.type_flag {
+            TypeFlag::Directory => self.directory(path, entry),
+            _ => self.call(path, &entry.meta, &entry.type_flag),
         }
-    } else {
-        Ok(res)
-    }
-}
-
-fn handle_dir_content(
-    current_item_path: &path::Path,
-    action: &Func,
-    nopenfd: i32,
-    flags: Flag,
-    ftw: &mut Ftw,
-    type_flag: &TypeFlag,
-) -> NftwResult {
-    match type_flag {
-        TypeFlag::SymlinkAbsentFile => {
-            let path_buf = fs::read_link(current_item_path)?;
-            if path_buf.metadata()?.is_dir() {
-                read_dir(path_buf.as_path(), action, nopenfd, flags, ftw)?;
+    }
+
+    fn is_other_mount(&self, meta: &io::Result<fs::Metadata>) -> bool {
+        match meta {
+            Ok(meta) => self.flags.contains(Flags::MOUNT) && meta.dev() != self.dev,
+            Err(_) => false,
+        }
+    }
+
+    /// Closes the oldest directories until there is a place for one more descriptor.
+    fn make_room(&mut self) -> io::Result<()> {
+        let mut open = self.frames.iter().filter(|x| x.fd().is_some()).count();
+        for frame in self.frames.iter_mut() {
+            if open < self.nopenfd.max(1) {
+                break;
             }
-            DEFALT_NFTW
-        },
-        TypeFlag::Directory | TypeFlag::PostReadableDirectory => read_dir(
-            current_item_path, action, nopenfd, flags, ftw
-        ),
-        _ => DEFALT_NFTW,
+
+            if frame.fd().is_some() {
+                frame.close()?;
+                open -= 1;
+            }
+        }
+        Ok(())
     }
-}
 
-fn _nftw(current_item_path: &path::Path, action: &Func, nopenfd: i32, flags: Flag, ftw: &mut Ftw) -> NftwResult {
-    let (out_meta, type_flag) = resolve_meta_and_type_flag(current_item_path, flags);
-    let callback_result = if FTW_DEPTH & flags == 0 {
-        action(&out_meta, &type_flag, ftw).and_then(|x| normalize(x, flags))?
-    } else {
-        CallbackResult::Continue
-    };
+    /// Descriptor of the innermost directory. The directory is reopened if it was closed.
+    fn frame_fd(&mut self) -> io::Result<RawFd> {
+        let last = self.frames.len() - 1;
+        if let Some(fd) = self.frames[last].fd() {
+            return Ok(fd);
+        }
 
-    match callback_result {
-        CallbackResult::SkipSubTree => return Ok(CallbackResult::Continue),
-        _ => (),
+        self.make_room()?;
+        let frame = &self.frames[last];
+        let relative = frame.path.strip_prefix(&self.root).unwrap_or(&frame.path);
+        let fd = reopen(&self.absolute_root.join(relative), frame.dev, frame.ino)?;
+        let raw = fd.raw();
+        self.frames[last].reopened = Some(fd);
+        Ok(raw)
     }
 
-    if out_meta.is_ok() {
-        handle_dir_content(current_item_path, action, nopenfd, flags, ftw, &type_flag)?;
+    /// Changes the working directory back to the parent of the directory left.
+    fn leave(&mut self) -> io::Result<()> {
+        if !self.flags.contains(Flags::CHDIR) {
+            return Ok(());
+        }
+
+        if self.frames.is_empty() {
+            return self.cwd.as_ref().map_or(Ok(()), Fd::fchdir);
+        }
+
+        let fd = self.frame_fd()?;
+        dir::fchdir(fd)
     }
 
-    // read_entry(&out_meta, &current_item_path, flags, &type_flag);
-    if FTW_DEPTH & flags != 0 {
-        action(&out_meta, &type_flag, ftw).and_then(|x| normalize(x, flags))
-    } else {
-        Ok(callback_result)
+    /// Whether a directory is one of the directories being walked. It happens if symbolic links
+    /// are followed.
+    fn is_cycle(&self, meta: &io::Result<fs::Metadata>) -> bool {
+        match meta {
+            Ok(meta) => self.frames.iter().any(|x| (x.dev, x.ino) == (meta.dev(), meta.ino())),
+            Err(_) => false,
+        }
+    }
+
+    fn directory(&mut self, path: &path::Path, entry: Entry) -> NftwResult {
+        let Entry { fd, meta, .. } = entry;
+        if self.is_cycle(&meta) {
+            return self.call(path, &meta, &TypeFlag::DirectoryCycle);
+        }
+
+        self.make_room()?;
+        let stream = match fd.as_ref().map(Dir::open) {
+            Some(Ok(x)) => x,
+            _ => return self.call(path, &meta, &TypeFlag::UnreadableDirectory),
+        };
+        drop(fd);
+
+        let is_depth = self.flags.contains(Flags::DEPTH);
+        if !is_depth {
+            match self.call(path, &meta, &TypeFlag::Directory)? {
+                CallbackResult::Continue => (),
+                // the content of the directory is skipped in both cases.
+                x => return Ok(x),
+            }
+        }
+
+        if self.flags.contains(Flags::CHDIR) {
+            dir::fchdir(stream.raw())?;
+        }
+
+        let (dev, ino) = meta.as_ref().map(|x| (x.dev(), x.ino())).unwrap_or_default();
+        let mut frame = Frame {
+            path: path.to_path_buf(), dev, ino, stream: Some(stream), reopened: None, pending: VecDeque::new(),
+        };
+
+        if self.sorted {
+            frame.read_ahead()?;
+            frame.pending.make_contiguous().sort();
+        }
+        self.frames.push(frame);
+
+        self.ftw.level += 1;
+        let res = self.read_frame();
+        self.ftw.level -= 1;
+        self.frames.pop();
+        res?;
+
+        // like nftw(3) the directory is reported while it's still the working directory.
+        let res = match is_depth {
+            true => self.call(path, &meta, &TypeFlag::PostReadableDirectory),
+            false => DEFALT_NFTW,
+        };
+        self.leave()?;
+        res
+    }
+
+    fn read_frame(&mut self) -> Result<(), NftwErr> {
+        loop {
+            let last = self.frames.len() - 1;
+            let name = match self.frames[last].next_name() {
+                Some(name) => name?,
+                None => return Ok(()),
+            };
+
+            let dirfd = self.frame_fd()?;
+            let path = self.frames[last].path.join(OsStr::from_bytes(name.to_bytes()));
+            let entry = resolve(dirfd, &name, self.flags);
+            if self.is_other_mount(&entry.meta) {
+                continue;
+            }
+
+            if let CallbackResult::SkipSiblings = self.entry(&path, entry)? {
+                return Ok(());
+            }
+        }
     }
 }
 
@@ -220,6 +560,270 @@ mod tests {
         let deep1 = TempDir::new(TempPath::Pathbuf(deep1_pathbuf));
     }
 
+    /// Tree in the temporary directory removed on drop.
+    pub(super) struct Fixture {
+        pub(super) root: path::PathBuf,
+    }
+
+    impl Fixture {
+        fn empty(name: &str) -> path::PathBuf {
+            let root = std::env::temp_dir().join(format!("nftw_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            root
+        }
+
+        /// Creates the tree:
+        /// root/a, root/d/b, root/d/e/, root/link_d -> d, root/dangling -> absent
+        pub(super) fn new(name: &str) -> Self {
+            let root = Self::empty(name);
+            fs::create_dir_all(root.join("d").join("e")).unwrap();
+            fs::write(root.join("a"), b"a").unwrap();
+            fs::write(root.join("d").join("b"), b"b").unwrap();
+            std::os::unix::fs::symlink("d", root.join("link_d")).unwrap();
+            std::os::unix::fs::symlink("absent", root.join("dangling")).unwrap();
+            Self { root }
+        }
+
+        /// Creates `width` directories of `width` directories of `width` files and a link
+        /// root/d0/up -> root.
+        pub(super) fn wide(name: &str, width: usize) -> Self {
+            let root = Self::empty(name);
+            for i in 0 .. width {
+                for j in 0 .. width {
+                    let dir = root.join(format!("d{}", i)).join(format!("d{}", j));
+                    fs::create_dir_all(&dir).unwrap();
+                    (0 .. width).for_each(|k| fs::write(dir.join(format!("f{}", k)), b"f").unwrap());
+                }
+            }
+            std::os::unix::fs::symlink("..", root.join("d0").join("up")).unwrap();
+            Self { root }
+        }
+
+        fn walk(&self, walker: &Walker) -> Result<Vec<(String, TypeFlag, u32)>, NftwErr> {
+            self.walk_with(walker, |_, _| CallbackResult::Continue)
+        }
+
+        fn walk_with<F>(&self, walker: &Walker, decide: F) -> Result<Vec<(String, TypeFlag, u32)>, NftwErr>
+        where
+            F: Fn(&str, &TypeFlag) -> CallbackResult
+        {
+            let mut visited = vec![];
+            walker.walk(&self.root, |path, _, type_flag, ftw| {
+                let relative = path.strip_prefix(&self.root).unwrap().to_str().unwrap().to_owned();
+                let res = decide(&relative, type_flag);
+                visited.push((relative, *type_flag, ftw.level));
+                Ok(res)
+            })?;
+            Ok(visited)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.root).unwrap();
+        }
+    }
+
+    fn find<'a>(visited: &'a [(String, TypeFlag, u32)], name: &str) -> Option<&'a (String, TypeFlag, u32)> {
+        visited.iter().find(|x| x.0 == name)
+    }
+
+    fn position(visited: &[(String, TypeFlag, u32)], name: &str) -> usize {
+        visited.iter().position(|x| x.0 == name).unwrap()
+    }
+
+    #[test]
+    fn follow_symlinks() {
+        let fixture = Fixture::new("follow_symlinks");
+        let visited = fixture.walk(&Walker::new()).unwrap();
+        assert_eq!(visited.len(), 9, "{:?}", visited);
+        assert_eq!(visited[0], ("".to_owned(), TypeFlag::Directory, 0));
+        assert_eq!(find(&visited, "a"), Some(&("a".to_owned(), TypeFlag::File, 1)));
+        assert_eq!(find(&visited, "d/e"), Some(&("d/e".to_owned(), TypeFlag::Directory, 2)));
+        assert_eq!(find(&visited, "link_d"), Some(&("link_d".to_owned(), TypeFlag::Directory, 1)));
+        assert_eq!(find(&visited, "link_d/b"), Some(&("link_d/b".to_owned(), TypeFlag::File, 2)));
+        assert_eq!(find(&visited, "dangling"), Some(&("dangling".to_owned(), TypeFlag::SymlinkAbsentFile, 1)));
+        assert!(position(&visited, "d") < position(&visited, "d/b"));
+    }
+
+    #[test]
+    fn physical() {
+        let fixture = Fixture::new("physical");
+        let visited = fixture.walk(&Walker::new().physical(true)).unwrap();
+        assert_eq!(visited.len(), 7, "{:?}", visited);
+        assert_eq!(find(&visited, "link_d"), Some(&("link_d".to_owned(), TypeFlag::Symlink, 1)));
+        assert_eq!(find(&visited, "dangling"), Some(&("dangling".to_owned(), TypeFlag::Symlink, 1)));
+        assert_eq!(find(&visited, "link_d/b"), None);
+    }
+
+    #[test]
+    fn depth() {
+        let fixture = Fixture::new("depth");
+        let visited = fixture.walk(&Walker::new().depth(true).physical(true)).unwrap();
+        assert_eq!(visited.len(), 7, "{:?}", visited);
+        assert_eq!(visited[6], ("".to_owned(), TypeFlag::PostReadableDirectory, 0));
+        assert_eq!(find(&visited, "d"), Some(&("d".to_owned(), TypeFlag::PostReadableDirectory, 1)));
+        assert!(position(&visited, "d") > position(&visited, "d/b"));
+        assert!(position(&visited, "d") > position(&visited, "d/e"));
+    }
+
+    #[test]
+    fn skip_subtree() {
+        let fixture = Fixture::new("skip_subtree");
+        let walker = Walker::new().physical(true).action_retval(true);
+        let visited = fixture.walk_with(&walker, |name, _| if name == "d" {
+            CallbackResult::SkipSubTree
+        } else {
+            CallbackResult::Continue
+        }).unwrap();
+        assert_eq!(visited.len(), 5, "{:?}", visited);
+        assert_eq!(find(&visited, "d/b"), None);
+        assert_eq!(find(&visited, "d/e"), None);
+    }
+
+    #[test]
+    fn skip_siblings() {
+        let fixture = Fixture::new("skip_siblings");
+        let walker = Walker::new().physical(true).action_retval(true);
+        let visited = fixture.walk_with(&walker, |name, _| if name.starts_with("d/") {
+            CallbackResult::SkipSiblings
+        } else {
+            CallbackResult::Continue
+        }).unwrap();
+        assert_eq!(visited.len(), 6, "{:?}", visited);
+        assert_eq!(visited.iter().filter(|x| x.0.starts_with("d/")).count(), 1);
+    }
+
+    #[test]
+    fn stop_without_action_retval() {
+        let fixture = Fixture::new("stop_without_action_retval");
+        let res = fixture.walk_with(&Walker::new(), |name, _| if name == "d" {
+            CallbackResult::SkipSubTree
+        } else {
+            CallbackResult::Continue
+        });
+        assert!(matches!(res, Err(NftwErr::Stop)));
+    }
+
+    #[test]
+    fn mount() {
+        let fixture = Fixture::new("mount");
+        std::os::unix::fs::symlink("/proc/self", fixture.root.join("proc")).unwrap();
+        let visited = fixture.walk(&Walker::new().mount(true)).unwrap();
+        assert_eq!(visited.len(), 9, "{:?}", visited);
+        assert_eq!(find(&visited, "proc"), None);
+
+        let visited = fixture.walk(&Walker::new().mount(true).physical(true)).unwrap();
+        assert_eq!(find(&visited, "proc"), Some(&("proc".to_owned(), TypeFlag::Symlink, 1)));
+    }
+
+    #[test]
+    fn chdir() {
+        let fixture = Fixture::new("chdir");
+        let initial = std::env::current_dir().unwrap();
+        let root = fixture.root.canonicalize().unwrap();
+        let mut cwds = vec![];
+        Walker::new().chdir(true).physical(true).walk(&fixture.root, |path, _, _, _| {
+            let relative = path.strip_prefix(&fixture.root).unwrap().to_str().unwrap().to_owned();
+            cwds.push((relative, std::env::current_dir().unwrap()));
+            DEFALT_NFTW
+        }).unwrap();
+
+        assert_eq!(std::env::current_dir().unwrap(), initial);
+        assert_eq!(cwds.len(), 7);
+        let cwd_of = |name: &str| cwds.iter().find(|x| x.0 == name).map(|x| x.1.clone());
+        assert_eq!(cwd_of(""), Some(initial));
+        assert_eq!(cwd_of("a"), Some(root.clone()));
+        assert_eq!(cwd_of("d"), Some(root.clone()));
+        assert_eq!(cwd_of("d/b"), Some(root.join("d")));
+        assert_eq!(cwd_of("d/e"), Some(root.join("d")));
+    }
+
+    #[test]
+    fn chdir_depth() {
+        let fixture = Fixture::new("chdir_depth");
+        let initial = std::env::current_dir().unwrap();
+        let root = fixture.root.canonicalize().unwrap();
+        let mut cwds = vec![];
+        Walker::new().chdir(true).depth(true).physical(true).walk(&fixture.root, |path, _, type_flag, ftw| {
+            if let TypeFlag::PostReadableDirectory = type_flag {
+                let relative = path.strip_prefix(&fixture.root).unwrap().to_str().unwrap().to_owned();
+                let base = path::PathBuf::from(OsStr::from_bytes(&path.as_os_str().as_bytes()[ftw.base as usize ..]));
+                cwds.push((relative, std::env::current_dir().unwrap(), base));
+            }
+            DEFALT_NFTW
+        }).unwrap();
+
+        assert_eq!(std::env::current_dir().unwrap(), initial);
+        // the directory being finished is the working directory.
+        let cwd_of = |name: &str| cwds.iter().find(|x| x.0 == name).map(|x| x.1.clone());
+        assert_eq!(cwd_of(""), Some(root.clone()));
+        assert_eq!(cwd_of("d"), Some(root.join("d")));
+        assert_eq!(cwd_of("d/e"), Some(root.join("d/e")));
+        assert_eq!(cwds.len(), 3);
+    }
+
+    #[test]
+    fn limited_descriptors() {
+        let fixture = Fixture::new("limited_descriptors");
+        let mut deep = fixture.root.join("d");
+        for level in 0 .. 8 {
+            deep.push(format!("l{}", level));
+            fs::create_dir(&deep).unwrap();
+            fs::write(deep.join("f"), b"f").unwrap();
+            fs::create_dir(deep.join("empty")).unwrap();
+        }
+
+        let mut expected = fixture.walk(&Walker::new()).unwrap();
+        expected.sort_by(|x, y| x.0.cmp(&y.0));
+        // the tree under `d` is visited through `link_d` as well.
+        assert_eq!(expected.len(), 9 + 8 * 3 * 2);
+        for nopenfd in 0 .. 3 {
+            let mut visited = fixture.walk(&Walker::new().nopenfd(nopenfd)).unwrap();
+            visited.sort_by(|x, y| x.0.cmp(&y.0));
+            assert_eq!(visited, expected, "nopenfd = {}", nopenfd);
+
+            let mut visited = fixture.walk(&Walker::new().nopenfd(nopenfd).chdir(true).depth(true)).unwrap();
+            visited.sort_by(|x, y| x.0.cmp(&y.0));
+            assert_eq!(visited.len(), expected.len(), "nopenfd = {}", nopenfd);
+        }
+    }
+
+    #[test]
+    fn cycle() {
+        let fixture = Fixture::new("cycle");
+        std::os::unix::fs::symlink("../..", fixture.root.join("d").join("e").join("up")).unwrap();
+        let visited = fixture.walk(&Walker::new()).unwrap();
+        assert_eq!(visited.len(), 11, "{:?}", visited);
+        assert_eq!(find(&visited, "d/e/up"), Some(&("d/e/up".to_owned(), TypeFlag::DirectoryCycle, 3)));
+        assert_eq!(find(&visited, "link_d/e/up"), Some(&("link_d/e/up".to_owned(), TypeFlag::DirectoryCycle, 3)));
+
+        let visited = fixture.walk(&Walker::new().physical(true)).unwrap();
+        assert_eq!(find(&visited, "d/e/up"), Some(&("d/e/up".to_owned(), TypeFlag::Symlink, 3)));
+    }
+
+    #[test]
+    fn base() {
+        assert_eq!(base_of(path::Path::new("/tmp/a")), 5);
+        assert_eq!(base_of(path::Path::new("/tmp/a/")), 5);
+        assert_eq!(base_of(path::Path::new("a")), 0);
+        assert_eq!(base_of(path::Path::new("/")), 0);
+
+        let fixture = Fixture::new("base");
+        Walker::new().walk(&fixture.root, |path, _, _, ftw| {
+            let bytes = path.as_os_str().as_bytes();
+            assert_eq!(Some(OsStr::from_bytes(&bytes[ftw.base as usize ..])), path.file_name());
+            DEFALT_NFTW
+        }).unwrap();
+    }
+
+    #[test]
+    fn absent_root() {
+        let res = Walker::new().walk("/nftw/absent/root", |_, _, _, _| DEFALT_NFTW);
+        assert!(matches!(res, Err(NftwErr::IO(ref e)) if e.kind() == io::ErrorKind::NotFound));
+    }
+
     // #[test]
     // fn test_nftw() {
     //     let dir_path = path::Path::new("test/right");
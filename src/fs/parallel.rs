@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+
+use super::absolute;
+use super::base_of;
+use super::normalize;
+use super::reopen;
+use super::resolve;
+use super::to_cstring;
+use super::CallbackResult;
+use super::Dir;
+use super::Entry;
+use super::Flags;
+use super::Ftw;
+use super::NftwErr;
+use super::NftwResult;
+use super::TypeFlag;
+use super::Walker;
+use super::DEFALT_NFTW;
+
+/// A directory which content is not reported yet.
+struct Task {
+    path: path::PathBuf,
+    meta: fs::Metadata,
+    // the directory opened when it's found. It's closed if there are `nopenfd` open already.
+    dir: Mutex<Option<Dir>>,
+    level: u32,
+    parent: Option<Arc<Task>>,
+    // the listing of the directory and its subdirectories which are not done yet.
+    remaining: AtomicUsize,
+}
+
+impl Task {
+    fn ancestors(self: &Arc<Self>) -> impl Iterator<Item=&Arc<Task>> {
+        let mut current = Some(self);
+        std::iter::from_fn(move || {
+            let task = current?;
+            current = task.parent.as_ref();
+            Some(task)
+        })
+    }
+}
+
+/// A place of a directory held open by a task in `Shared::open`. It's freed on drop.
+struct Slot<'s>(&'s AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Queue {
+    tasks: VecDeque<Arc<Task>>,
+    active: usize, // queued and running tasks
+}
+
+struct Shared<'a, F> {
+    flags: Flags,
+    sorted: bool,
+    nopenfd: usize,
+    open: AtomicUsize, // directories held open by tasks
+    dev: u64,
+    root: &'a path::Path,
+    absolute_root: path::PathBuf, // to reopen directories closed to fit `nopenfd`
+    action: &'a F,
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    stopped: AtomicBool,
+    error: Mutex<Option<NftwErr>>,
+}
+
+impl<'a, F> Shared<'a, F>
+where
+    F: Fn(&path::Path, &io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult + Sync,
+{
+    fn call(&self, path: &path::Path, meta: &io::Result<fs::Metadata>, type_flag: &TypeFlag, level: u32) -> NftwResult {
+        let ftw = Ftw { base: base_of(path), level };
+        let res = (self.action)(path, meta, type_flag, &ftw)?;
+        normalize(res, self.flags)
+    }
+
+    fn submit(&self, task: Arc<Task>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.tasks.push_back(task);
+        queue.active += 1;
+        self.ready.notify_one();
+    }
+
+    fn fail(&self, e: NftwErr) {
+        self.error.lock().unwrap().get_or_insert(e);
+        let _queue = self.queue.lock().unwrap();
+        self.stopped.store(true, Ordering::SeqCst);
+        self.ready.notify_all();
+    }
+
+    fn next_task(&self) -> Option<Arc<Task>> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            if let Some(task) = queue.tasks.pop_front() {
+                return Some(task);
+            }
+
+            if queue.active == 0 {
+                return None;
+            }
+
+            queue = self.ready.wait(queue).unwrap();
+        }
+    }
+
+    fn work(&self) {
+        while let Some(task) = self.next_task() {
+            if let Err(e) = self.list(&task) {
+                self.fail(e);
+            }
+
+            let mut queue = self.queue.lock().unwrap();
+            queue.active -= 1;
+            if queue.active == 0 {
+                self.ready.notify_all();
+            }
+        }
+    }
+
+    fn list(&self, task: &Arc<Task>) -> Result<(), NftwErr> {
+        let held = task.dir.lock().unwrap().take();
+        let slot = held.as_ref().map(|_| Slot(&self.open));
+        let mut stream = match held {
+            Some(stream) => stream,
+            None => {
+                let relative = task.path.strip_prefix(self.root).unwrap_or(&task.path);
+                let fd = reopen(&self.absolute_root.join(relative), task.meta.dev(), task.meta.ino())?;
+                Dir::open(&fd)?
+            },
+        };
+
+        let mut names = vec![];
+        while let Some(name) = stream.read() {
+            names.push(name?);
+        }
+
+        if self.sorted {
+            names.sort();
+        }
+
+        for name in names {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            let path = task.path.join(OsStr::from_bytes(name.to_bytes()));
+            let entry = resolve(stream.raw(), &name, self.flags);
+            match entry.meta {
+                Ok(ref meta) if self.flags.contains(Flags::MOUNT) && meta.dev() != self.dev => continue,
+                _ => (),
+            }
+
+            let res = match entry.type_flag {
+                TypeFlag::Directory => self.directory(Some(task), &path, entry, task.level + 1)?,
+                _ => self.call(&path, &entry.meta, &entry.type_flag, task.level + 1)?,
+            };
+
+            if let CallbackResult::SkipSiblings = res {
+                break;
+            }
+        }
+
+        drop(stream);
+        drop(slot);
+        self.finish(task)
+    }
+
+    /// Reports a directory and submits its content to the workers.
+    fn directory(&self, parent: Option<&Arc<Task>>, path: &path::Path, entry: Entry, level: u32) -> NftwResult {
+        let Entry { fd, meta, .. } = entry;
+        let is_cycle = match (&meta, parent) {
+            (Ok(meta), Some(parent)) => parent.ancestors()
+                .any(|x| (x.meta.dev(), x.meta.ino()) == (meta.dev(), meta.ino())),
+            _ => false,
+        };
+
+        if is_cycle {
+            return self.call(path, &meta, &TypeFlag::DirectoryCycle, level);
+        }
+
+        // the directory is read by a worker later, the stream is passed to it.
+        let (stream, meta_ok) = match (fd.as_ref().map(Dir::open), &meta) {
+            (Some(Ok(stream)), Ok(x)) => (stream, x.clone()),
+            _ => return self.call(path, &meta, &TypeFlag::UnreadableDirectory, level),
+        };
+
+        if !self.flags.contains(Flags::DEPTH) {
+            match self.call(path, &meta, &TypeFlag::Directory, level)? {
+                CallbackResult::Continue => (),
+                x => return Ok(x),
+            }
+        }
+
+        if let Some(parent) = parent {
+            parent.remaining.fetch_add(1, Ordering::SeqCst);
+        }
+
+        // the worker reopens the directory by its path if it's closed here.
+        let limit = self.nopenfd.max(1);
+        let is_held = self.open
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| Some(x + 1).filter(|x| *x <= limit))
+            .is_ok();
+        self.submit(Arc::new(Task {
+            path: path.to_path_buf(),
+            meta: meta_ok,
+            dir: Mutex::new(Some(stream).filter(|_| is_held)),
+            level,
+            parent: parent.cloned(),
+            remaining: AtomicUsize::new(1),
+        }));
+        DEFALT_NFTW
+    }
+
+    /// Marks the listing of `task` done. Directories are reported after all their content with
+    /// `DEPTH` so ancestors which are done as well are reported too.
+    fn finish(&self, task: &Arc<Task>) -> Result<(), NftwErr> {
+        for task in task.ancestors() {
+            if task.remaining.fetch_sub(1, Ordering::SeqCst) != 1 {
+                break;
+            }
+
+            if self.flags.contains(Flags::DEPTH) {
+                let meta = Ok(task.meta.clone());
+                self.call(&task.path, &meta, &TypeFlag::PostReadableDirectory, task.level)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Walker {
+    /// Walks the tree like `walk` but subdirectories are read by `threads` workers so `action` is
+    /// called from several threads at once. Entries of a directory are reported by one worker in
+    /// the order `sorted` sets. `CHDIR` can't be used as the working directory is shared by all
+    /// threads. With `DEPTH` a directory is reported by the worker finishing the last of its
+    /// subdirectories so `SkipSiblings` returned for it has no effect. Queued directories are kept
+    /// open up to `nopenfd`, the rest are opened again by their paths when they are read.
+    pub fn walk_parallel<P, F>(&self, dirname: P, threads: usize, action: F) -> NftwResult
+    where
+        P: AsRef<path::Path>,
+        F: Fn(&path::Path, &io::Result<fs::Metadata>, &TypeFlag, &Ftw) -> NftwResult + Sync,
+    {
+        if self.flags.contains(Flags::CHDIR) {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "CHDIR can't be used by a parallel walk");
+            return Err(NftwErr::IO(e));
+        }
+
+        let dirname = dirname.as_ref();
+        let entry = resolve(libc::AT_FDCWD, &to_cstring(dirname)?, self.flags);
+        let dev = match entry.meta {
+            Ok(ref meta) => meta.dev(),
+            Err(e) => return Err(NftwErr::IO(e)),
+        };
+
+        let shared = Shared {
+            flags: self.flags,
+            sorted: self.sorted,
+            nopenfd: self.nopenfd,
+            open: AtomicUsize::new(0),
+            dev,
+            root: dirname,
+            absolute_root: absolute(dirname)?,
+            action: &action,
+            queue: Mutex::new(Queue { tasks: VecDeque::new(), active: 0 }),
+            ready: Condvar::new(),
+            stopped: AtomicBool::new(false),
+            error: Mutex::new(None),
+        };
+
+        match entry.type_flag {
+            TypeFlag::Directory => shared.directory(None, dirname, entry, 0)?,
+            _ => shared.call(dirname, &entry.meta, &entry.type_flag, 0)?,
+        };
+
+        thread::scope(|scope| {
+            for _ in 0 .. threads.max(1) {
+                scope.spawn(|| shared.work());
+            }
+        });
+
+        match shared.error.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => DEFALT_NFTW,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use super::super::tests::Fixture;
+
+    type Listing = BTreeMap<String, Vec<(String, TypeFlag, u32)>>;
+
+    fn record(listing: &Mutex<Listing>, path: &path::Path, type_flag: &TypeFlag, ftw: &Ftw) {
+        let parent = path.parent().unwrap().to_str().unwrap().to_owned();
+        let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+        listing.lock().unwrap().entry(parent).or_default().push((name, *type_flag, ftw.level));
+    }
+
+    fn serial(walker: &Walker, root: &path::Path) -> Listing {
+        let listing = Mutex::new(Listing::new());
+        walker.walk(root, |path, _, type_flag, ftw| {
+            record(&listing, path, type_flag, ftw);
+            DEFALT_NFTW
+        }).unwrap();
+        listing.into_inner().unwrap()
+    }
+
+    fn parallel(walker: &Walker, root: &path::Path, threads: usize) -> Listing {
+        let listing = Mutex::new(Listing::new());
+        walker.walk_parallel(root, threads, |path, _, type_flag, ftw| {
+            record(&listing, path, type_flag, ftw);
+            DEFALT_NFTW
+        }).unwrap();
+        listing.into_inner().unwrap()
+    }
+
+    #[test]
+    fn same_as_serial() {
+        let fixture = Fixture::wide("parallel_same_as_serial", 4);
+        let walker = Walker::new().sorted(true);
+        let expected = serial(&walker, &fixture.root);
+        assert_eq!(expected.values().map(|x| x.len()).sum::<usize>(), 1 + 4 + 4 * 4 + 4 * 4 * 4 + 1);
+        for threads in 1 .. 5 {
+            assert_eq!(parallel(&walker, &fixture.root, threads), expected, "threads = {}", threads);
+        }
+    }
+
+    #[test]
+    fn limited_descriptors() {
+        let fixture = Fixture::wide("parallel_limited_descriptors", 3);
+        let expected = serial(&Walker::new().sorted(true), &fixture.root);
+        for nopenfd in 0 .. 3 {
+            let walker = Walker::new().sorted(true).nopenfd(nopenfd);
+            assert_eq!(parallel(&walker, &fixture.root, 4), expected, "nopenfd = {}", nopenfd);
+        }
+    }
+
+    #[test]
+    fn depth() {
+        let fixture = Fixture::wide("parallel_depth", 3);
+        let order = Mutex::new(vec![]);
+        Walker::new().depth(true).walk_parallel(&fixture.root, 4, |path, _, type_flag, _| {
+            order.lock().unwrap().push((path.to_path_buf(), *type_flag));
+            DEFALT_NFTW
+        }).unwrap();
+
+        let order = order.into_inner().unwrap();
+        assert_eq!(order.len(), 1 + 3 + 3 * 3 + 3 * 3 * 3 + 1);
+        assert_eq!(order.last(), Some(&(fixture.root.clone(), TypeFlag::PostReadableDirectory)));
+        for (i, (path, _)) in order.iter().enumerate() {
+            let parent_position = order.iter().position(|x| Some(x.0.as_path()) == path.parent());
+            assert!(parent_position.is_none_or(|x| x > i), "{} is reported after its parent", path.display());
+        }
+    }
+
+    #[test]
+    fn stop() {
+        let fixture = Fixture::wide("parallel_stop", 3);
+        let calls = AtomicUsize::new(0);
+        let res = Walker::new().walk_parallel(&fixture.root, 4, |_, _, type_flag, _| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            match type_flag {
+                TypeFlag::File => Err(NftwErr::Stop),
+                _ => DEFALT_NFTW,
+            }
+        });
+        assert!(matches!(res, Err(NftwErr::Stop)));
+        assert!(calls.load(Ordering::SeqCst) < 1 + 3 + 3 * 3 + 3 * 3 * 3 + 1);
+    }
+
+    #[test]
+    fn chdir() {
+        let res = Walker::new().chdir(true).walk_parallel("/tmp", 2, |_, _, _, _| DEFALT_NFTW);
+        assert!(matches!(res, Err(NftwErr::IO(ref e)) if e.kind() == io::ErrorKind::InvalidInput));
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::graph::DepthFirstOrder;
+use crate::graph::Digraph;
+use crate::graph::Edge;
+
+/// Strongly connected components found by the Kosaraju-Sharir algorithm. Vertices are mutually
+/// reachable if and only if they are in the same component.
+pub struct StrongComponents {
+    id: Vec<usize>,
+    count: usize,
+}
+
+impl StrongComponents {
+    pub fn new(graph: &Digraph) -> Self {
+        // sources taken in reverse postorder of the reverse graph keep every search in one component.
+        let order = DepthFirstOrder::new(&graph.reverse()).reverse_post();
+        let mut id = vec![None; graph.len()];
+        let mut count = 0;
+        for source in order {
+            if id[source].is_some() {
+                continue;
+            }
+
+            id[source] = Some(count);
+            let mut stack = vec![source];
+            while let Some(vertex) = stack.pop() {
+                for edge in graph.adj(vertex) {
+                    if id[edge.to].is_none() {
+                        id[edge.to] = Some(count);
+                        stack.push(edge.to);
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        Self { id: id.into_iter().map(|x| x.expect("every vertex is visited")).collect(), count }
+    }
+
+    /// Number of components.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Component of `vertex` between 0 and `count`.
+    pub fn component_of(&self, vertex: usize) -> usize {
+        self.id[vertex]
+    }
+
+    pub fn strongly_connected(&self, v: usize, w: usize) -> bool {
+        self.id[v] == self.id[w]
+    }
+
+    /// Vertices of every component in increasing order. Components with several vertices or with
+    /// a self loop are cycles.
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut components = vec![Vec::new(); self.count];
+        self.id.iter().enumerate().for_each(|(vertex, id)| components[*id].push(vertex));
+        components
+    }
+
+    /// Builds a graph of components which has an edge between two components if `graph` has one
+    /// between their vertices. The weight is the smallest weight of such edges. There are no edges
+    /// within components, so the graph is acyclic.
+    pub fn condensation(&self, graph: &Digraph) -> Digraph {
+        let mut weights: HashMap<(usize, usize), f32> = HashMap::new();
+        let mut pairs = Vec::new();
+        for edge in (0 .. graph.len()).flat_map(|x| graph.adj(x)) {
+            let pair = (self.id[edge.from], self.id[edge.to]);
+            if pair.0 == pair.1 {
+                continue;
+            }
+
+            // order of edges follows the original graph.
+            let weight = weights.entry(pair).or_insert_with(|| { pairs.push(pair); edge.weight });
+            *weight = weight.min(edge.weight);
+        }
+
+        let mut condensation = Digraph::new(self.count);
+        pairs.into_iter().for_each(|(from, to)| condensation.add(Edge{ from, to, weight: weights[&(from, to)] }));
+        condensation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::digraph::build;
+    use crate::graph::find_cycle;
+
+    // tinyDG of Algorithms by Sedgewick and Wayne. Edges between components are the heavy ones.
+    const GRAPH: &[(usize, usize, f32)] = &[
+        (4, 2, 0.5), (2, 3, 0.5), (3, 2, 0.5), (6, 0, 2.), (0, 1, 1.), (2, 0, 0.5), (11, 12, 0.5),
+        (12, 9, 0.5), (9, 10, 0.5), (9, 11, 0.5), (7, 9, 3.), (10, 12, 0.5), (11, 4, 4.), (4, 3, 0.5),
+        (3, 5, 0.5), (6, 8, 0.5), (8, 6, 0.5), (5, 4, 0.5), (0, 5, 0.5), (6, 4, 1.5), (6, 9, 5.),
+        (7, 6, 6.),
+    ];
+
+    #[test]
+    fn components() {
+        let graph = build(13, GRAPH);
+        let components = StrongComponents::new(&graph);
+        assert_eq!(components.count(), 5);
+        let mut groups = components.components();
+        groups.sort();
+        assert_eq!(groups, vec![vec![0, 2, 3, 4, 5], vec![1], vec![6, 8], vec![7], vec![9, 10, 11, 12]]);
+        assert!(components.strongly_connected(0, 4));
+        assert!(components.strongly_connected(9, 12));
+        assert!(!components.strongly_connected(6, 7));
+        assert_eq!(components.component_of(10), components.component_of(11));
+
+        let empty = StrongComponents::new(&Digraph::new(0));
+        assert_eq!(empty.count(), 0);
+    }
+
+    #[test]
+    fn condensation() {
+        let graph = build(13, GRAPH);
+        let components = StrongComponents::new(&graph);
+        let condensation = components.condensation(&graph);
+        assert_eq!(condensation.len(), 5);
+        assert!(find_cycle(&condensation).is_none());
+
+        let component = |x| components.component_of(x);
+        let edges: Vec<(usize, usize, f32)> = (0 .. 5).flat_map(|x| condensation.adj(x))
+            .map(|x| (x.from, x.to, x.weight)).collect();
+        // 6 -> 0 and 6 -> 4 are merged into one edge of the smaller weight.
+        assert_eq!(edges.len(), 6);
+        assert!(edges.contains(&(component(6), component(0), 1.5)));
+        assert!(edges.contains(&(component(11), component(4), 4.)));
+        assert!(edges.contains(&(component(7), component(6), 6.)));
+        assert!(edges.contains(&(component(0), component(1), 1.)));
+        assert!(edges.contains(&(component(6), component(9), 5.)));
+    }
+}
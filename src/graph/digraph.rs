@@ -39,6 +39,28 @@ impl Digraph {
     pub fn len(&self) -> usize {
         self.vertex_count
     }
+
+    /// Copy of the graph with every edge turned backwards keeping its weight.
+    pub fn reverse(&self) -> Self {
+        let mut reversed = Self::new(self.vertex_count);
+        (0 .. self.vertex_count).flat_map(|x| self.adj(x))
+            .for_each(|x| reversed.add(Edge{ from: x.to, to: x.from, weight: x.weight }));
+        reversed
+    }
+}
+
+/// Builds a graph of `(from, to, weight)` triples.
+#[cfg(test)]
+pub(crate) fn build(vertex_count: usize, edges: &[(usize, usize, f32)]) -> Digraph {
+    let mut graph = Digraph::new(vertex_count);
+    edges.iter().for_each(|(from, to, weight)| graph.add(Edge{ from: *from, to: *to, weight: *weight }));
+    graph
+}
+
+/// Ends of `edges` which are easier to compare than edges.
+#[cfg(test)]
+pub(crate) fn pairs<'a>(edges: impl IntoIterator<Item=&'a Edge>) -> Vec<(usize, usize)> {
+    edges.into_iter().map(|x| (x.from, x.to)).collect()
 }
 
 #[cfg(test)]
@@ -60,4 +82,18 @@ mod tests {
         let from0 = dig.adj(0).collect::<Vec<&Edge>>();
         assert_eq!(from0, expected_edges.iter().collect::<Vec<&Edge>>());
     }
+
+    #[test]
+    fn reverse() {
+        let mut dig = Digraph::new(3);
+        dig.add(Edge{from: 0, to: 1, weight: 0.1});
+        dig.add(Edge{from: 0, to: 2, weight: 0.2});
+        dig.add(Edge{from: 1, to: 1, weight: 0.3});
+
+        let reversed = dig.reverse();
+        assert_eq!(reversed.len(), 3);
+        assert_eq!(reversed.adj(0).count(), 0);
+        assert_eq!(reversed.adj(1).collect::<Vec<_>>(), vec![&Edge{from: 1, to: 0, weight: 0.1}, &Edge{from: 1, to: 1, weight: 0.3}]);
+        assert_eq!(reversed.adj(2).collect::<Vec<_>>(), vec![&Edge{from: 2, to: 0, weight: 0.2}]);
+    }
 }
@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::graph::Digraph;
+use crate::graph::Edge;
+use crate::graph::FlowEdge;
+use crate::graph::FlowNetwork;
+
+mod writer;
+
+/// Line and column of a character starting from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum DotError {
+    IO(io::Error),
+    Syntax(Position, String),
+    /// An edge doesn't have a label which can be a weight or a capacity.
+    Label(Position, String),
+    /// Names of several nodes are the same number like `7` and `007`.
+    Node(String),
+}
+
+impl fmt::Display for DotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DotError::IO(e) => write!(f, "fail reading dot file: {}", e),
+            DotError::Syntax(position, x) => write!(f, "syntax error at {}: {}", position, x),
+            DotError::Label(position, x) => write!(f, "incorrect label at {}: {}", position, x),
+            DotError::Node(x) => write!(f, "incorrect node: {}", x),
+        }
+    }
+}
+
+impl From<io::Error> for DotError {
+    fn from(e: io::Error) -> Self {
+        DotError::IO(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Identifier, number, quoted or HTML string.
+    Id(String),
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Semicolon,
+    Comma,
+    Equal,
+    Arrow,
+    /// Edge of an undirected graph.
+    Line,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    position: Position,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Lexer { chars: src.chars().peekable(), position: Position { line: 1, column: 1 } }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        match c {
+            '\n' => {
+                self.position.line += 1;
+                self.position.column = 1;
+            },
+            _ => self.position.column += 1,
+        }
+        Some(c)
+    }
+
+    fn error<T>(&self, position: Position, msg: impl AsRef<str>) -> Result<T, DotError> {
+        Err(DotError::Syntax(position, msg.as_ref().to_owned()))
+    }
+
+    /// Skips white space and comments including `#` lines of the preprocessor. `#` can't start a
+    /// token, so it starts a comment anywhere.
+    fn skip_blank(&mut self) -> Result<(), DotError> {
+        loop {
+            let start = self.position;
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                },
+                Some('#') => while self.bump().is_some_and(|x| x != '\n') {},
+                Some('/') => {
+                    self.bump();
+                    match self.bump() {
+                        Some('/') => while self.chars.peek().is_some_and(|x| *x != '\n') {
+                            self.bump();
+                        },
+                        Some('*') => {
+                            let mut previous = ' ';
+                            loop {
+                                match self.bump() {
+                                    Some('/') if previous == '*' => break,
+                                    Some(c) => previous = c,
+                                    None => return self.error(start, "unclosed comment"),
+                                }
+                            }
+                        },
+                        _ => return self.error(start, "unexpected `/`"),
+                    }
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn quoted(&mut self, start: Position) -> Result<String, DotError> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some('"') => value.push('"'),
+                    Some('\n') => (),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    },
+                    None => return self.error(start, "unclosed string"),
+                },
+                Some(c) => value.push(c),
+                None => return self.error(start, "unclosed string"),
+            }
+        }
+    }
+
+    /// Reads an HTML string which angle brackets are balanced.
+    fn html(&mut self, start: Position) -> Result<String, DotError> {
+        let mut value = String::new();
+        let mut depth = 1;
+        loop {
+            let c = match self.bump() {
+                Some(c) => c,
+                None => return self.error(start, "unclosed HTML string"),
+            };
+            match c {
+                '<' => depth += 1,
+                '>' if depth == 1 => return Ok(value),
+                '>' => depth -= 1,
+                _ => (),
+            }
+            value.push(c);
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Position)>, DotError> {
+        self.skip_blank()?;
+        let start = self.position;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let token = match c {
+            '{' => Token::LeftBrace,
+            '}' => Token::RightBrace,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '=' => Token::Equal,
+            '"' => Token::Id(self.quoted(start)?),
+            '<' => Token::Id(self.html(start)?),
+            '-' if self.chars.peek() == Some(&'>') => {
+                self.bump();
+                Token::Arrow
+            },
+            '-' if self.chars.peek() == Some(&'-') => {
+                self.bump();
+                Token::Line
+            },
+            c if is_id_char(c) || c == '-' => {
+                let mut value = c.to_string();
+                while let Some(c) = self.chars.peek().copied().filter(|x| is_id_char(*x)) {
+                    value.push(c);
+                    self.bump();
+                }
+                Token::Id(value)
+            },
+            c => return self.error(start, format!("unexpected `{}`", c)),
+        };
+        Ok(Some((token, start)))
+    }
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Position)>, DotError> {
+    let mut lexer = Lexer::new(src);
+    let mut tokens = vec![];
+    while let Some(token) = lexer.next_token()? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+type Attributes = HashMap<String, String>;
+
+/// Default attributes set by `node [...]` and `edge [...]` in a graph or a subgraph.
+#[derive(Clone, Default)]
+struct Defaults {
+    node: Attributes,
+    edge: Attributes,
+}
+
+/// Edge of a graph read from the DOT language. Vertices are indices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DotEdge {
+    pub from: usize,
+    pub to: usize,
+    pub attributes: HashMap<String, String>,
+    /// Position of the edge operator.
+    pub position: Position,
+}
+
+struct RawEdge {
+    from: String,
+    to: String,
+    attributes: Attributes,
+    position: Position,
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    next: usize,
+    end: Position,
+    /// Names of nodes in the order of appearance with their attributes.
+    nodes: Vec<(String, Attributes)>,
+    node_index: HashMap<String, usize>,
+    edges: Vec<RawEdge>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|x| &x.0)
+    }
+
+    fn position(&self) -> Position {
+        self.tokens.get(self.next).map_or(self.end, |x| x.1)
+    }
+
+    fn error<T>(&self, msg: impl AsRef<str>) -> Result<T, DotError> {
+        Err(DotError::Syntax(self.position(), msg.as_ref().to_owned()))
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.next += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), DotError> {
+        match self.eat(&token) {
+            true => Ok(()),
+            false => self.error(format!("expected {}", what)),
+        }
+    }
+
+    fn id(&mut self, what: &str) -> Result<String, DotError> {
+        match self.peek() {
+            Some(Token::Id(x)) => {
+                let x = x.clone();
+                self.next += 1;
+                Ok(x)
+            },
+            _ => self.error(format!("expected {}", what)),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(x)) if x.eq_ignore_ascii_case(keyword))
+    }
+
+    fn graph(&mut self) -> Result<(), DotError> {
+        if self.is_keyword("strict") {
+            self.next += 1;
+        }
+        if self.is_keyword("graph") {
+            return self.error("undirected graphs are not supported");
+        }
+        if !self.is_keyword("digraph") {
+            return self.error("expected `digraph`");
+        }
+        self.next += 1;
+        if let Some(Token::Id(_)) = self.peek() {
+            self.next += 1;
+        }
+        self.expect(Token::LeftBrace, "`{`")?;
+        self.statements(Defaults::default())?;
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("unexpected content after the graph"),
+        }
+    }
+
+    /// Parses statements up to the closing brace. Returns nodes mentioned.
+    fn statements(&mut self, mut defaults: Defaults) -> Result<Vec<String>, DotError> {
+        let mut nodes = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::RightBrace) => {
+                    self.next += 1;
+                    return Ok(nodes);
+                },
+                Some(Token::Semicolon) | Some(Token::Comma) => self.next += 1,
+                None => return self.error("expected `}`"),
+                _ => nodes.extend(self.statement(&mut defaults)?),
+            }
+        }
+    }
+
+    fn statement(&mut self, defaults: &mut Defaults) -> Result<Vec<String>, DotError> {
+        for (keyword, is_node) in [("node", Some(true)), ("edge", Some(false)), ("graph", None)] {
+            let is_attributes = self.is_keyword(keyword)
+                && matches!(self.tokens.get(self.next + 1), Some((Token::LeftBracket, _)));
+            if !is_attributes {
+                continue;
+            }
+            self.next += 1;
+            let attributes = self.attributes()?;
+            match is_node {
+                Some(true) => defaults.node.extend(attributes),
+                Some(false) => defaults.edge.extend(attributes),
+                None => (),
+            }
+            return Ok(vec![]);
+        }
+
+        // an attribute of the graph like `rankdir="LR"`.
+        if let (Some((Token::Id(_), _)), Some((Token::Equal, _))) = (self.tokens.get(self.next), self.tokens.get(self.next + 1)) {
+            self.next += 2;
+            self.id("a value of the attribute")?;
+            return Ok(vec![]);
+        }
+
+        let mut operands = vec![self.operand(defaults)?];
+        let mut operators = vec![];
+        while let Some(Token::Arrow) | Some(Token::Line) = self.peek() {
+            if self.peek() == Some(&Token::Line) {
+                return self.error("`--` is only allowed in undirected graphs");
+            }
+            operators.push(self.position());
+            self.next += 1;
+            operands.push(self.operand(defaults)?);
+        }
+
+        let attributes = match self.peek() {
+            Some(Token::LeftBracket) => self.attributes()?,
+            _ => Attributes::new(),
+        };
+
+        if operators.is_empty() {
+            // a node statement sets attributes of the node itself.
+            for name in &operands[0] {
+                self.add_node(name, &defaults.node, &attributes);
+            }
+            return Ok(operands.swap_remove(0));
+        }
+
+        for (i, position) in operators.into_iter().enumerate() {
+            for from in &operands[i] {
+                for to in &operands[i + 1] {
+                    let mut merged = defaults.edge.clone();
+                    merged.extend(attributes.clone());
+                    self.edges.push(RawEdge { from: from.clone(), to: to.clone(), attributes: merged, position });
+                }
+            }
+        }
+        Ok(operands.into_iter().flatten().collect())
+    }
+
+    /// A node or a subgraph which nodes are all the operand.
+    fn operand(&mut self, defaults: &Defaults) -> Result<Vec<String>, DotError> {
+        if self.is_keyword("subgraph") {
+            self.next += 1;
+            if let Some(Token::Id(_)) = self.peek() {
+                self.next += 1;
+            }
+            self.expect(Token::LeftBrace, "`{`")?;
+            return self.statements(defaults.clone());
+        }
+        if self.eat(&Token::LeftBrace) {
+            return self.statements(defaults.clone());
+        }
+
+        let name = self.id("a node, a subgraph or an attribute")?;
+        self.add_node(&name, &defaults.node, &Attributes::new());
+        Ok(vec![name])
+    }
+
+    /// Parses `[a=b, c=d; ...]` which can be repeated like `[a=b][c=d]`.
+    fn attributes(&mut self) -> Result<Attributes, DotError> {
+        let mut attributes = Attributes::new();
+        while self.eat(&Token::LeftBracket) {
+            loop {
+                if self.eat(&Token::RightBracket) {
+                    break;
+                }
+                let name = self.id("an attribute name or `]`")?;
+                self.expect(Token::Equal, "`=`")?;
+                let value = self.id("a value of the attribute")?;
+                attributes.insert(name, value);
+                if !self.eat(&Token::Comma) {
+                    self.eat(&Token::Semicolon);
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Registers the node on the first appearance. Attributes of later statements override the
+    /// previous ones.
+    fn add_node(&mut self, name: &str, defaults: &Attributes, attributes: &Attributes) {
+        let i = match self.node_index.get(name) {
+            Some(x) => *x,
+            None => {
+                self.nodes.push((name.to_owned(), defaults.clone()));
+                self.node_index.insert(name.to_owned(), self.nodes.len() - 1);
+                self.nodes.len() - 1
+            },
+        };
+        self.nodes[i].1.extend(attributes.clone());
+    }
+}
+
+fn is_invisible(attributes: &Attributes) -> bool {
+    attributes.get("style").is_some_and(|x| x.contains("invis"))
+}
+
+/// Graph read from the DOT language of Graphviz.
+///
+/// Nodes which names are numbers less than the number of nodes get the numbers as indices, the
+/// rest of nodes are numbered after them in the order of appearance, so `0 -> 1 -> target` makes
+/// `target` the vertex 2.
+/// Invisible nodes and edges (`style=invis`) are layout helpers like legends and they aren't
+/// parts of the graph.
+///
+/// # Examples
+///
+/// ```ignore
+/// let dot = Dot::parse("digraph { source -> 0 [label=0.5] }").unwrap();
+/// let graph = dot.digraph().unwrap();
+/// assert_eq!(dot.index("source"), Some(1));
+/// assert_eq!(graph.adj(1).next().map(|x| (x.to, x.weight)), Some((0, 0.5)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Dot {
+    names: Vec<Option<String>>,
+    index: HashMap<String, usize>,
+    edges: Vec<DotEdge>,
+}
+
+impl Dot {
+    pub fn parse(src: &str) -> Result<Self, DotError> {
+        let tokens = tokenize(src)?;
+        let end = src.lines().enumerate().last().map_or(Position { line: 1, column: 1 }, |(i, x)| {
+            Position { line: i + 1, column: x.chars().count() + 1 }
+        });
+        let mut parser = Parser { tokens, next: 0, end, nodes: vec![], node_index: HashMap::new(), edges: vec![] };
+        parser.graph()?;
+
+        let visible: Vec<&String> = parser.nodes.iter().filter(|x| !is_invisible(&x.1)).map(|x| &x.0).collect();
+        // larger numbers would leave gaps, so they are numbered like other names.
+        let numbered = |x: &&String| x.bytes().all(|x| x.is_ascii_digit())
+            .then(|| x.parse::<usize>().ok()).flatten()
+            .filter(|x| *x < visible.len());
+        let mut names: Vec<Option<String>> = vec![None; visible.iter().filter_map(numbered).max().map_or(0, |x| x + 1)];
+        for name in &visible {
+            match numbered(name) {
+                Some(i) => if let Some(other) = names[i].replace(name.to_string()) {
+                    return Err(DotError::Node(format!("`{}` and `{}` are both the vertex {}", other, name, i)));
+                },
+                None => names.push(Some(name.to_string())),
+            }
+        }
+        let index: HashMap<String, usize> = names.iter().enumerate()
+            .filter_map(|(i, x)| x.as_ref().map(|x| (x.clone(), i)))
+            .collect();
+
+        // edges of invisible nodes are the only ones without indices.
+        let edges = parser.edges.into_iter().filter(|x| !is_invisible(&x.attributes)).filter_map(|x| {
+            Some(DotEdge { from: *index.get(&x.from)?, to: *index.get(&x.to)?, attributes: x.attributes, position: x.position })
+        }).collect();
+        Ok(Dot { names, index, edges })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DotError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Number of vertices.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    /// Name of the vertex. `None` for numbers skipped in the graph.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).and_then(|x| x.as_deref())
+    }
+
+    pub fn edges(&self) -> &[DotEdge] {
+        &self.edges
+    }
+
+    fn label<'a>(&self, edge: &'a DotEdge) -> Result<&'a str, DotError> {
+        edge.attributes.get("label").map(String::as_str).ok_or_else(|| DotError::Label(
+            edge.position,
+            format!("edge `{}` has no label", self.edge_name(edge)),
+        ))
+    }
+
+    fn edge_name(&self, edge: &DotEdge) -> String {
+        format!("{} -> {}", self.name(edge.from).unwrap_or("?"), self.name(edge.to).unwrap_or("?"))
+    }
+
+    fn number<T: std::str::FromStr>(&self, edge: &DotEdge, value: &str) -> Result<T, DotError> {
+        value.trim().parse().map_err(|_| DotError::Label(
+            edge.position,
+            format!("`{}` of edge `{}` is not a number", value, self.edge_name(edge)),
+        ))
+    }
+
+    /// Builds a digraph which weights are labels of edges.
+    pub fn digraph(&self) -> Result<Digraph, DotError> {
+        let mut graph = Digraph::new(self.len());
+        for edge in &self.edges {
+            let weight = self.number(edge, self.label(edge)?)?;
+            graph.add(Edge { from: edge.from, to: edge.to, weight });
+        }
+        Ok(graph)
+    }
+
+    /// Builds a flow network which capacities are labels of edges. Labels can be `flow/capacity`
+    /// like drawings of a computed flow, the flow is dropped then. `inf` means no limit.
+    pub fn flow_network(&self) -> Result<FlowNetwork, DotError> {
+        let mut network = FlowNetwork::new(self.len());
+        for edge in &self.edges {
+            let label = self.label(edge)?;
+            let capacity = label.rsplit('/').next().unwrap_or(label);
+            network.add(FlowEdge::new(edge.from, edge.to, self.number(edge, capacity)?));
+        }
+        Ok(network)
+    }
+}
+
+/// Loads a file of `assets`.
+#[cfg(test)]
+pub(crate) fn load_asset(path: &str) -> Dot {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(path);
+    Dot::load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_error(src: &str) -> (Position, String) {
+        match Dot::parse(src) {
+            Err(DotError::Syntax(position, x)) => (position, x),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let dot = Dot::parse(r#"
+            /* weights */ digraph G {
+                edge [color=red]
+                0 -> 2 -> target [label="0.5"];
+                source -> {0; 2} [label=.1][color=blue]
+                # preprocessor line
+                legend [style=invis]
+                legend -> 0 [label="x \" y"]
+                2 [shape=box]
+            }"#).unwrap();
+        assert_eq!(dot.len(), 5);
+        assert_eq!(dot.name(1), None);
+        assert_eq!(dot.index("target"), Some(3));
+        assert_eq!(dot.index("source"), Some(4));
+        assert_eq!(dot.index("legend"), None);
+
+        let edges: Vec<(usize, usize, &str)> = dot.edges().iter()
+            .map(|x| (x.from, x.to, x.attributes["label"].as_str())).collect();
+        assert_eq!(edges, vec![(0, 2, "0.5"), (2, 3, "0.5"), (4, 0, ".1"), (4, 2, ".1")]);
+        assert_eq!(dot.edges()[0].attributes["color"], "red");
+        assert_eq!(dot.edges()[2].attributes["color"], "blue");
+        assert_eq!(dot.edges()[1].position, Position { line: 4, column: 24 });
+
+        let graph = dot.digraph().unwrap();
+        assert_eq!(graph.adj(4).map(|x| x.weight).collect::<Vec<f32>>(), vec![0.1, 0.1]);
+        let network = dot.flow_network().unwrap();
+        assert_eq!(network.adj(2).count(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(syntax_error("digraph {\n  a -> \n}"), (Position { line: 3, column: 1 }, "expected a node, a subgraph or an attribute".to_owned()));
+        assert_eq!(syntax_error("digraph {\n  a [label=\"x]\n}").0, Position { line: 2, column: 12 });
+        assert_eq!(syntax_error("digraph { a ! b }").0, Position { line: 1, column: 13 });
+        assert_eq!(syntax_error("digraph { a -> b").0, Position { line: 1, column: 17 });
+        assert!(syntax_error("graph { a -- b }").1.contains("undirected"));
+        assert!(syntax_error("digraph { } x").1.contains("after the graph"));
+        assert!(syntax_error("digraph { /* a -> b }").1.contains("comment"));
+
+        let dot = Dot::parse("digraph {\n  a -> b\n  b -> c [label=heavy]\n}").unwrap();
+        match dot.digraph() {
+            Err(DotError::Label(position, x)) => {
+                assert_eq!(position, Position { line: 2, column: 5 });
+                assert_eq!(x, "edge `a -> b` has no label");
+            },
+            x => panic!("{:?}", x.err()),
+        }
+        let dot = Dot::parse("digraph { a -> b [label=\"1/x\"] }").unwrap();
+        let e = dot.flow_network().err().unwrap();
+        assert_eq!(e.to_string(), "incorrect label at 1:13: `x` of edge `a -> b` is not a number");
+        assert!(matches!(Dot::load("/nonexistent.dot"), Err(DotError::IO(_))));
+
+        let e = Dot::parse("digraph { 1 -> 007 -> 7; a; b; c; d; e; f }").err().unwrap();
+        assert_eq!(e.to_string(), "incorrect node: `007` and `7` are both the vertex 7");
+    }
+
+    #[test]
+    fn example() {
+        // the example of `Dot` which isn't compiled as a doc test in a binary crate.
+        let dot = Dot::parse("digraph { source -> 0 [label=0.5] }").unwrap();
+        let graph = dot.digraph().unwrap();
+        assert_eq!(dot.index("source"), Some(1));
+        assert_eq!(graph.adj(1).next().map(|x| (x.to, x.weight)), Some((0, 0.5)));
+    }
+
+    #[test]
+    fn large_numbers() {
+        let dot = Dot::parse("digraph { 99999999999 -> a; 1 -> 3 }").unwrap();
+        assert_eq!(dot.len(), 6);
+        assert_eq!((dot.index("1"), dot.index("3")), (Some(1), Some(3)));
+        assert_eq!((dot.index("99999999999"), dot.index("a")), (Some(4), Some(5)));
+        let edges: Vec<(usize, usize)> = dot.edges().iter().map(|x| (x.from, x.to)).collect();
+        assert_eq!(edges, vec![(4, 5), (1, 3)]);
+    }
+
+    #[test]
+    fn assets() {
+        let dot = load_asset("maxflow/bipartial_dance/basic.dot");
+        assert_eq!((dot.index("source"), dot.index("target"), dot.len()), (Some(10), Some(11), 12));
+        assert_eq!(dot.edges().len(), 20);
+        dot.flow_network().unwrap();
+
+        // the legend is invisible.
+        let dot = load_asset("shortest_right_descending.dot");
+        assert_eq!(dot.len(), 5);
+        dot.digraph().unwrap();
+
+        let dot = load_asset("maxflow/basic.dot");
+        assert_eq!(dot.edges().len(), 15);
+        dot.flow_network().unwrap();
+    }
+
+    #[test]
+    fn all_assets_parse() {
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("assets")];
+        let mut count = 0;
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|x| x == "dot") {
+                    Dot::load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+                    count += 1;
+                }
+            }
+        }
+        assert!(count > 10);
+    }
+}
@@ -0,0 +1,159 @@
+use std::ptr;
+
+use crate::graph::Digraph;
+use crate::graph::Edge;
+use crate::graph::FlowNetwork;
+
+/// Colors of highlighted paths. The first path is green like the right paths in `assets`.
+const PATH_COLORS: &[&str] = &["green", "blue", "red", "orange", "purple"];
+/// Fill color of vertices in the cut of the source like in `assets/maxflow/basic.dot`.
+const CUT_FILL: &str = "#aaaaaa";
+/// Color of edges crossing the minimum cut.
+const CUT_EDGE_COLOR: &str = "blue";
+
+/// Builder of a DOT document in the layout of `assets`.
+struct Writer {
+    out: String,
+}
+
+fn attribute_list(attributes: &[(&str, String)]) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+
+    let list: Vec<String> = attributes.iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, value.replace('"', "\\\"")))
+        .collect();
+    format!(" [{}]", list.join(", "))
+}
+
+impl Writer {
+    fn new() -> Self {
+        Writer { out: "digraph {\n\trankdir=\"LR\";  // horizontal layout\n".to_owned() }
+    }
+
+    fn node(&mut self, vertex: usize, attributes: &[(&str, String)]) {
+        self.out += &format!("\t{}{};\n", vertex, attribute_list(attributes));
+    }
+
+    fn edge(&mut self, from: usize, to: usize, attributes: &[(&str, String)]) {
+        self.out += &format!("\t{} -> {}{};\n", from, to, attribute_list(attributes));
+    }
+
+    fn finish(mut self) -> String {
+        self.out += "}\n";
+        self.out
+    }
+}
+
+impl Digraph {
+    /// Renders the graph to DOT. Weights are labels of edges.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with_paths(&[])
+    }
+
+    /// Renders the graph to DOT highlighting `paths` which are made of edges of the graph. Every
+    /// path has its own color, an edge of several paths gets all their colors.
+    pub fn to_dot_with_paths(&self, paths: &[Vec<&Edge>]) -> String {
+        let mut writer = Writer::new();
+        let mut touched = vec![false; self.len()];
+        (0 .. self.len()).flat_map(|x| self.adj(x)).for_each(|x| { touched[x.from] = true; touched[x.to] = true; });
+        // isolated vertices would be lost otherwise.
+        (0 .. self.len()).filter(|x| !touched[*x]).for_each(|x| writer.node(x, &[]));
+        for edge in (0 .. self.len()).flat_map(|x| self.adj(x)) {
+            let mut attributes = vec![("label", edge.weight.to_string())];
+            let colors: Vec<&str> = paths.iter().enumerate()
+                .filter(|(_, path)| path.iter().any(|x| ptr::eq(*x, edge)))
+                .map(|(i, _)| PATH_COLORS[i % PATH_COLORS.len()])
+                .collect();
+            if !colors.is_empty() {
+                attributes.push(("color", colors.join(":")));
+            }
+            writer.edge(edge.from, edge.to, &attributes);
+        }
+        writer.finish()
+    }
+}
+
+impl FlowNetwork {
+    /// Renders the network to DOT. Labels of edges are `flow/capacity`.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with_cut(&[])
+    }
+
+    /// Renders the network to DOT showing a cut. Vertices marked in `marked` are filled with gray
+    /// and edges from them to unmarked vertices are blue.
+    pub fn to_dot_with_cut(&self, marked: &[bool]) -> String {
+        let is_marked = |x: usize| marked.get(x).copied().unwrap_or(false);
+        let mut writer = Writer::new();
+        for vertex in 0 .. self.len() {
+            match is_marked(vertex) {
+                true => writer.node(vertex, &[("fillcolor", CUT_FILL.to_owned()), ("style", "filled".to_owned())]),
+                // adjacency of a network lists both ends of an edge.
+                false if self.adj(vertex).next().is_none() => writer.node(vertex, &[]),
+                false => (),
+            }
+        }
+        for edge in self.edges() {
+            let mut attributes = vec![("label", format!("{}/{}", edge.flow(), edge.capacity()))];
+            if is_marked(edge.from()) && !is_marked(edge.to()) {
+                attributes.push(("color", CUT_EDGE_COLOR.to_owned()));
+            }
+            writer.edge(edge.from(), edge.to(), &attributes);
+        }
+        writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Dot;
+    use crate::graph::FlowEdge;
+
+    #[test]
+    fn digraph() {
+        let mut graph = Digraph::new(4);
+        graph.add(Edge { from: 0, to: 1, weight: 0.5 });
+        graph.add(Edge { from: 1, to: 2, weight: 1.25 });
+        graph.add(Edge { from: 0, to: 2, weight: 2. });
+        let expected = "digraph {
+\trankdir=\"LR\";  // horizontal layout
+\t3;
+\t0 -> 1 [label=\"0.5\"];
+\t0 -> 2 [label=\"2\"];
+\t1 -> 2 [label=\"1.25\"];
+}
+";
+        assert_eq!(graph.to_dot(), expected);
+
+        let first: Vec<&Edge> = graph.adj(0).take(1).chain(graph.adj(1)).collect();
+        let second: Vec<&Edge> = graph.adj(0).skip(1).collect();
+        let dot = graph.to_dot_with_paths(&[first, second.clone(), second]);
+        assert!(dot.contains("\t0 -> 1 [label=\"0.5\", color=\"green\"];\n"));
+        assert!(dot.contains("\t0 -> 2 [label=\"2\", color=\"blue:red\"];\n"));
+
+        // the output is readable back.
+        let dot = Dot::parse(&dot).unwrap();
+        assert_eq!(dot.len(), 4);
+        let read = dot.digraph().unwrap();
+        let weights: Vec<f32> = (0 .. 4).flat_map(|x| read.adj(x).map(|x| x.weight)).collect();
+        assert_eq!(weights, vec![0.5, 2., 1.25]);
+    }
+
+    #[test]
+    fn flow_network() {
+        let mut net = FlowNetwork::new(3);
+        net.add(FlowEdge::new(0, 1, 2.));
+        net.add(FlowEdge::new(1, 2, 1.));
+        net.edge_mut(0).unwrap().add_residual_flow_to(1, 1.).unwrap();
+        let dot = net.to_dot_with_cut(&[true, true, false]);
+        assert!(dot.contains("\t0 [fillcolor=\"#aaaaaa\", style=\"filled\"];\n"));
+        assert!(dot.contains("\t0 -> 1 [label=\"1/2\"];\n"));
+        assert!(dot.contains("\t1 -> 2 [label=\"0/1\", color=\"blue\"];\n"));
+        assert!(!net.to_dot().contains("color"));
+
+        let read = Dot::parse(&dot).unwrap().flow_network().unwrap();
+        assert_eq!(read.edge(0).unwrap().capacity(), 2.);
+    }
+}
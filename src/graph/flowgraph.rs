@@ -20,6 +20,22 @@ impl FlowEdge {
         Self { from, to, flow: 0., capacity }
     }
 
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
+    pub fn to(&self) -> usize {
+        self.to
+    }
+
+    pub fn flow(&self) -> f64 {
+        self.flow
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.capacity
+    }
+
     pub fn other(&self, than: usize) -> Result<usize, String> {
         if than == self.from {
             Ok(self.to)
@@ -94,6 +110,11 @@ impl FlowNetwork {
         self.edge_store.get_mut(index)
     }
 
+    /// Every edge once in the order of adding.
+    pub fn edges(&self) -> impl Iterator<Item=&FlowEdge> {
+        self.edge_store.iter()
+    }
+
     pub fn adj(&self, vertex: usize) -> impl Iterator<Item=&usize> {
         self.edges[vertex].iter()
     }
@@ -7,6 +7,7 @@ use crate::graph::FlowEdge;
 type Edges<'a> = Vec<Option<&'a FlowEdge>>;
 
 struct FordFulkerson<'a> {
+    net: &'a FlowNetwork,
     edge_to: Edges<'a>,
     maxflow: f64, // this is mincut as well.
     marked: Vec<bool>, // every true stays with `from` (A cut), every false stays with `to` (B cut)
@@ -68,7 +69,12 @@ impl<'a> FordFulkerson<'a> {
         };
 
         let netref: &FlowNetwork = net;
-        Ok(Self { marked, edge_to: edge_to.into_iter().map(|x| x.and_then(|i| netref.edge(i))).collect(), maxflow })
+        Ok(Self { net: netref, marked, edge_to: edge_to.into_iter().map(|x| x.and_then(|i| netref.edge(i))).collect(), maxflow })
+    }
+
+    /// Renders the network to DOT with the flow through every edge and the minimum cut.
+    fn to_dot(&self) -> String {
+        self.net.to_dot_with_cut(&self.marked)
     }
 
 }
@@ -99,6 +105,7 @@ fn has_augumenting_path(net: &FlowNetwork, from: usize, to: usize) -> Result<(Ve
 mod tests {
     use super::*;
     use std::fmt::Debug;
+    use crate::graph::dot::load_asset;
 
     fn assert_res<T, E: Debug>(res: Result<T, E>) -> T {
         match res {
@@ -139,38 +146,15 @@ mod tests {
 
     #[test]
     fn basic() {
-
-        // see assets/maxflow/basic.dot
         let source = 0;
         let target = 7;
-        let edges = vec![
-            FlowEdge::new(source, 1, 10.),
-            FlowEdge::new(source, 2, 5.),
-            FlowEdge::new(source, 3, 15.),
-
-            FlowEdge::new(1, 2, 4.),
-            FlowEdge::new(2, 3, 4.),
-
-            FlowEdge::new(1, 5, 15.),
-
-            FlowEdge::new(1, 4, 9.),
-            FlowEdge::new(2, 5, 8.),
-            FlowEdge::new(3, 6, 16.),
-
-            FlowEdge::new(6, 2, 6.),
-
-            FlowEdge::new(4, 5, 15.),
-            FlowEdge::new(5, 6, 15.),
-
-            FlowEdge::new(4, target, 10.),
-            FlowEdge::new(5, target, 10.),
-            FlowEdge::new(6, target, 10.),
-        ];
-
-        let mut net = FlowNetwork::new(8);
-        edges.into_iter().for_each(|x| net.add(x));
+        let mut net = load_asset("maxflow/basic.dot").flow_network().unwrap();
         let ff = assert_res(FordFulkerson::new(&mut net, source, target));
-        let FordFulkerson{ edge_to, maxflow, marked } = ff;
+        let dot = ff.to_dot();
+        assert!(dot.contains("\t2 [fillcolor=\"#aaaaaa\", style=\"filled\"];\n"));
+        assert!(dot.contains("\t0 -> 1 [label=\"10/10\", color=\"blue\"];\n"));
+        assert!(dot.contains("\t1 -> 5 [label=\"2/15\"];\n"));
+        let FordFulkerson{ edge_to, maxflow, marked, .. } = ff;
 
         assert!(marked[0]);
         assert!(marked[2]);
@@ -266,6 +250,15 @@ mod tests {
             }
         }
 
+        #[test]
+        fn asset() {
+            let dot = load_asset("maxflow/bipartial_dance/basic.dot");
+            let (source, target) = (dot.index("source").unwrap(), dot.index("target").unwrap());
+            let mut net = dot.flow_network().unwrap();
+            let ff = assert_res(FordFulkerson::new(&mut net, source, target));
+            assert_eq!(ff.maxflow, 5.);
+            assert_eq!(ff.marked.iter().filter(|x| **x).count(), 1);
+        }
     }
 
     mod find_the_biggest_closure {
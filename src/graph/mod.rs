@@ -1,10 +1,26 @@
+mod components;
 mod digraph;
+mod dot;
 mod shortest_path;
 mod flowgraph;
 mod maxflow;
+mod order;
 
+pub use components::StrongComponents;
 pub use digraph::Digraph;
 pub use digraph::Edge;
+pub use dot::Dot;
+pub use dot::DotEdge;
+pub use dot::DotError;
+pub use dot::Position;
+pub use shortest_path::AcyclicPaths;
+pub use shortest_path::BellmanFord;
 pub use shortest_path::Dijkstra;
+pub use shortest_path::KShortestPaths;
+pub use shortest_path::Monotonic;
+pub use shortest_path::PathError;
 pub use flowgraph::FlowNetwork;
 pub use flowgraph::FlowEdge;
+pub use order::DepthFirstOrder;
+pub use order::find_cycle;
+pub use order::topological_order;
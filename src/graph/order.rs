@@ -0,0 +1,159 @@
+use crate::graph::Digraph;
+use crate::graph::Edge;
+
+/// Orders of vertices visited by depth-first search over the whole graph. Search starts from
+/// vertices in increasing order and follows edges in the order of adding. The stack is kept on the
+/// heap, so deep graphs don't overflow the thread stack.
+pub struct DepthFirstOrder {
+    pre: Vec<usize>,
+    post: Vec<usize>,
+}
+
+impl DepthFirstOrder {
+    pub fn new(graph: &Digraph) -> Self {
+        let mut marked = vec![false; graph.len()];
+        let mut pre = Vec::with_capacity(graph.len());
+        let mut post = Vec::with_capacity(graph.len());
+        for source in 0 .. graph.len() {
+            if marked[source] {
+                continue;
+            }
+
+            marked[source] = true;
+            pre.push(source);
+            let mut stack = vec![(source, graph.adj(source))];
+            while let Some((vertex, edges)) = stack.last_mut() {
+                match edges.next() {
+                    Some(edge) if !marked[edge.to] => {
+                        marked[edge.to] = true;
+                        pre.push(edge.to);
+                        stack.push((edge.to, graph.adj(edge.to)));
+                    },
+                    Some(_) => (),
+                    None => {
+                        post.push(*vertex);
+                        stack.pop();
+                    },
+                }
+            }
+        }
+
+        Self { pre, post }
+    }
+
+    /// Vertices in the order of the first visit.
+    pub fn pre(&self) -> &[usize] {
+        &self.pre
+    }
+
+    /// Vertices in the order of finishing, after all vertices reachable from them.
+    pub fn post(&self) -> &[usize] {
+        &self.post
+    }
+
+    /// Vertices in reverse postorder which is a topological order if the graph has no cycles.
+    pub fn reverse_post(&self) -> Vec<usize> {
+        self.post.iter().rev().copied().collect()
+    }
+}
+
+/// Finds a directed cycle. Its edges go in the order of traversal, so the last one returns to the
+/// start of the first one.
+pub fn find_cycle(graph: &Digraph) -> Option<Vec<&Edge>> {
+    let mut marked = vec![false; graph.len()];
+    let mut on_stack = vec![false; graph.len()];
+    let mut edge_to: Vec<Option<&Edge>> = vec![None; graph.len()];
+    for source in 0 .. graph.len() {
+        if marked[source] {
+            continue;
+        }
+
+        marked[source] = true;
+        on_stack[source] = true;
+        let mut stack = vec![(source, graph.adj(source))];
+        while let Some((vertex, edges)) = stack.last_mut() {
+            match edges.next() {
+                Some(edge) if on_stack[edge.to] => {
+                    let mut cycle = vec![edge];
+                    let mut current = edge.from;
+                    while current != edge.to {
+                        let previous = edge_to[current].expect("a vertex on the stack has an edge to it");
+                        cycle.push(previous);
+                        current = previous.from;
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                },
+                Some(edge) if !marked[edge.to] => {
+                    marked[edge.to] = true;
+                    on_stack[edge.to] = true;
+                    edge_to[edge.to] = Some(edge);
+                    stack.push((edge.to, graph.adj(edge.to)));
+                },
+                Some(_) => (),
+                None => {
+                    on_stack[*vertex] = false;
+                    stack.pop();
+                },
+            }
+        }
+    }
+
+    None
+}
+
+/// Orders vertices so every edge goes forward. Fails with a cycle if there is no such order.
+pub fn topological_order(graph: &Digraph) -> Result<Vec<usize>, Vec<&Edge>> {
+    match find_cycle(graph) {
+        Some(cycle) => Err(cycle),
+        None => Ok(DepthFirstOrder::new(graph).reverse_post()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::digraph::build;
+    use crate::graph::digraph::pairs;
+    use crate::graph::dot::load_asset;
+
+    fn dag() -> Digraph {
+        load_asset("algs4/tinyDAG.dot").digraph().unwrap()
+    }
+
+    #[test]
+    fn depth_first_order() {
+        let graph = build(5, &[(0, 1, 1.), (1, 2, 1.), (0, 2, 1.), (3, 2, 1.), (3, 4, 1.)]);
+        let order = DepthFirstOrder::new(&graph);
+        assert_eq!(order.pre(), &[0, 1, 2, 3, 4]);
+        assert_eq!(order.post(), &[2, 1, 0, 4, 3]);
+        assert_eq!(order.reverse_post(), vec![3, 4, 0, 1, 2]);
+        assert!(DepthFirstOrder::new(&Digraph::new(0)).pre().is_empty());
+    }
+
+    #[test]
+    fn topological() {
+        let graph = dag();
+        let order = topological_order(&graph).unwrap();
+        assert_eq!(order.len(), 13);
+        let mut position = [0; 13];
+        order.iter().enumerate().for_each(|(i, x)| position[*x] = i);
+        assert!((0 .. 13).flat_map(|x| graph.adj(x)).all(|x| position[x.from] < position[x.to]));
+
+        // a long chain doesn't overflow the stack.
+        let chain: Vec<(usize, usize, f32)> = (0 .. 99_999).map(|x| (x, x + 1, 1.)).collect();
+        assert_eq!(topological_order(&build(100_000, &chain)).unwrap()[99_999], 99_999);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut graph = dag();
+        graph.add(Edge{ from: 4, to: 7, weight: 1. });
+        let cycle = topological_order(&graph).unwrap_err();
+        assert_eq!(pairs(cycle), vec![(6, 4), (4, 7), (7, 6)]);
+
+        let self_loop = build(2, &[(0, 1, 1.), (1, 1, 1.)]);
+        assert_eq!(find_cycle(&self_loop).unwrap().len(), 1);
+        assert!(find_cycle(&dag()).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+use std::f32::INFINITY;
+use std::f32::NEG_INFINITY;
+use std::vec;
+
+use crate::graph::Digraph;
+use crate::graph::Edge;
+use crate::graph::topological_order;
+use super::PathError;
+
+/// Shortest or longest paths in a graph without cycles. Vertices are relaxed in topological order,
+/// so it takes linear time and allows negative weights.
+pub struct AcyclicPaths<'a> {
+    edge_to: Vec<Option<&'a Edge>>,
+    dist_to: Vec<f32>,
+    /// Distance to vertices without paths. It's the worst one.
+    unreached: f32,
+}
+
+impl<'a> AcyclicPaths<'a> {
+    pub fn shortest(graph: &'a Digraph, source: usize) -> Result<Self, PathError> {
+        Self::new(graph, source, false)
+    }
+
+    /// Finds longest paths like critical paths of jobs which edges are durations.
+    pub fn longest(graph: &'a Digraph, source: usize) -> Result<Self, PathError> {
+        Self::new(graph, source, true)
+    }
+
+    fn new(graph: &'a Digraph, source: usize, longest: bool) -> Result<Self, PathError> {
+        if source >= graph.len() {
+            return Err(PathError::InvalidSource(source));
+        }
+
+        let order = topological_order(graph)
+            .map_err(|cycle| PathError::Cycle(cycle.iter().map(|x| x.from).collect()))?;
+        let (unreached, better): (f32, fn(f32, f32) -> bool) = match longest {
+            true => (NEG_INFINITY, |x, y| x > y),
+            false => (INFINITY, |x, y| x < y),
+        };
+        let mut edge_to = vec![None; graph.len()];
+        let mut dist_to = vec![unreached; graph.len()];
+        dist_to[source] = 0.;
+        // vertices before the source in the order are unreachable from it.
+        for vertex in order.into_iter().skip_while(|x| *x != source) {
+            if dist_to[vertex] == unreached {
+                continue;
+            }
+
+            for edge in graph.adj(vertex) {
+                let distance = dist_to[vertex] + edge.weight;
+                if better(distance, dist_to[edge.to]) {
+                    dist_to[edge.to] = distance;
+                    edge_to[edge.to] = Some(edge);
+                }
+            }
+        }
+
+        Ok(Self { edge_to, dist_to, unreached })
+    }
+
+    fn edges_to(&self, target: usize) -> impl Iterator<Item=&'a Edge> + '_ {
+        let mut current = self.edge_to.get(target).and_then(|x| *x);
+        std::iter::from_fn(move || {
+            let edge = current.take()?;
+            current = self.edge_to[edge.from];
+            Some(edge)
+        })
+    }
+
+    /// Edges of the path from the source to `target`. It's empty if there is no path or `target`
+    /// is the source.
+    pub fn path_to(&self, target: usize) -> vec::IntoIter<&'a Edge> {
+        let mut path: Vec<&'a Edge> = self.edges_to(target).collect();
+        path.reverse();
+        path.into_iter()
+    }
+
+    /// Distance from the source to `target`. It's infinite for shortest paths and negative
+    /// infinite for longest ones if there is no path.
+    pub fn dist_to(&self, target: usize) -> f32 {
+        self.dist_to.get(target).copied().unwrap_or(self.unreached)
+    }
+
+    pub fn has_path_to(&self, target: usize) -> bool {
+        self.dist_to(target) != self.unreached
+    }
+
+    /// Renders `graph` which the paths are found in to DOT highlighting the path to `target` or
+    /// the whole tree of paths if there is no target.
+    pub fn to_dot(&self, graph: &Digraph, target: Option<usize>) -> String {
+        let path = match target {
+            Some(target) => self.edges_to(target).collect(),
+            None => self.edge_to.iter().filter_map(|x| *x).collect(),
+        };
+        graph.to_dot_with_paths(&[path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::digraph::build;
+    use crate::graph::digraph::pairs;
+    use crate::graph::dot::load_asset;
+
+    fn dag() -> Digraph {
+        load_asset("algs4/tinyEWDAG.dot").digraph().unwrap()
+    }
+
+    #[test]
+    fn shortest() {
+        let graph = dag();
+        let paths = AcyclicPaths::shortest(&graph, 5).unwrap();
+        assert_eq!(pairs(paths.path_to(0)), vec![(5, 4), (4, 0)]);
+        assert!((paths.dist_to(0) - 0.73).abs() < 1e-5);
+        assert!((paths.dist_to(6) - 1.13).abs() < 1e-5);
+        assert_eq!(paths.dist_to(5), 0.);
+        assert!(paths.has_path_to(5));
+        assert!(AcyclicPaths::shortest(&graph, 8).is_err());
+
+        let paths = AcyclicPaths::shortest(&graph, 3).unwrap();
+        assert!(!paths.has_path_to(5));
+        assert_eq!(paths.dist_to(5), INFINITY);
+        assert_eq!(paths.path_to(5).next(), None);
+    }
+
+    #[test]
+    fn longest() {
+        let graph = dag();
+        let paths = AcyclicPaths::longest(&graph, 5).unwrap();
+        assert_eq!(pairs(paths.path_to(2)), vec![(5, 1), (1, 3), (3, 6), (6, 4), (4, 7), (7, 2)]);
+        assert!((paths.dist_to(2) - 2.77).abs() < 1e-5);
+        assert_eq!(AcyclicPaths::longest(&graph, 3).unwrap().dist_to(5), NEG_INFINITY);
+    }
+
+    #[test]
+    fn critical_path() {
+        // jobs with durations and jobs which must be done before. A job is a pair of vertices of
+        // its start and its end, the last two vertices are the start and the end of the schedule.
+        let jobs: &[(f32, &[usize])] = &[(41., &[1, 7, 9]), (51., &[2]), (50., &[]), (36., &[]), (38., &[]),
+            (45., &[]), (21., &[3, 8]), (32., &[3, 8]), (32., &[2]), (29., &[4, 6])];
+        let (start, end) = (jobs.len() * 2, jobs.len() * 2 + 1);
+        let mut edges = Vec::new();
+        for (job, (duration, successors)) in jobs.iter().enumerate() {
+            edges.push((job, job + jobs.len(), *duration));
+            edges.push((start, job, 0.));
+            edges.push((job + jobs.len(), end, 0.));
+            successors.iter().for_each(|x| edges.push((job + jobs.len(), *x, 0.)));
+        }
+
+        let graph = build(jobs.len() * 2 + 2, &edges);
+        let paths = AcyclicPaths::longest(&graph, start).unwrap();
+        assert_eq!(paths.dist_to(end), 173.);
+        let critical: Vec<usize> = paths.path_to(end).filter(|x| x.weight > 0.).map(|x| x.from).collect();
+        assert_eq!(critical, vec![0, 9, 6, 8, 2]);
+        // start times of jobs.
+        assert_eq!(paths.dist_to(1), 41.);
+        assert_eq!(paths.dist_to(3), 91.);
+    }
+
+    #[test]
+    fn cycle() {
+        let graph = build(3, &[(0, 1, 1.), (1, 2, 1.), (2, 1, 1.)]);
+        let error = AcyclicPaths::longest(&graph, 0).err().unwrap();
+        assert_eq!(error, PathError::Cycle(vec![1, 2]));
+        assert_eq!(error.to_string(), "graph has a cycle 1 -> 2 -> 1");
+    }
+}
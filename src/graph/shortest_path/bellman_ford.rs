@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::f32::INFINITY;
+use std::vec;
+
+use crate::graph::Digraph;
+use crate::graph::Edge;
+use super::PathError;
+
+/// Shortest paths in a graph with negative weights. Only vertices which distances changed on the
+/// previous pass are relaxed, so the running time is E * V in the worst case but E + V usually.
+pub struct BellmanFord<'a> {
+    edge_to: Vec<Option<&'a Edge>>,
+    dist_to: Vec<f32>,
+    cycle: Option<Vec<&'a Edge>>,
+}
+
+impl<'a> BellmanFord<'a> {
+    /// Finds shortest paths from `source` to every vertex or a negative cycle reachable from it.
+    pub fn new(graph: &'a Digraph, source: usize) -> Result<Self, PathError> {
+        if source >= graph.len() {
+            return Err(PathError::InvalidSource(source));
+        }
+
+        let mut shortest_path = Self {
+            edge_to: vec![None; graph.len()],
+            dist_to: vec![INFINITY; graph.len()],
+            cycle: None,
+        };
+        shortest_path.dist_to[source] = 0.;
+        let mut on_queue = vec![false; graph.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back(source);
+        on_queue[source] = true;
+        let mut relaxations = 0;
+        while let Some(vertex) = queue.pop_front() {
+            on_queue[vertex] = false;
+            for edge in graph.adj(vertex) {
+                let distance = shortest_path.dist_to[vertex] + edge.weight;
+                if shortest_path.dist_to[edge.to] > distance {
+                    shortest_path.dist_to[edge.to] = distance;
+                    shortest_path.edge_to[edge.to] = Some(edge);
+                    if !on_queue[edge.to] {
+                        queue.push_back(edge.to);
+                        on_queue[edge.to] = true;
+                    }
+                }
+
+                // a negative cycle makes relaxing endless, it's looked for once per V relaxations.
+                relaxations += 1;
+                if relaxations % graph.len() == 0 {
+                    shortest_path.cycle = shortest_path.find_cycle();
+                    if shortest_path.cycle.is_some() {
+                        return Ok(shortest_path);
+                    }
+                }
+            }
+        }
+
+        Ok(shortest_path)
+    }
+
+    /// Looks for a cycle among the edges of the tree of shortest paths. Any cycle there is a
+    /// negative one.
+    fn find_cycle(&self) -> Option<Vec<&'a Edge>> {
+        // every vertex has one edge at most, so a walk from a vertex either ends or loops.
+        let mut walked_from = vec![None; self.edge_to.len()];
+        for start in 0 .. self.edge_to.len() {
+            let mut vertex = start;
+            while walked_from[vertex].is_none() {
+                walked_from[vertex] = Some(start);
+                match self.edge_to[vertex] {
+                    Some(edge) => vertex = edge.from,
+                    None => break,
+                }
+            }
+
+            if walked_from[vertex] == Some(start) && self.edge_to[vertex].is_some() {
+                return Some(self.cycle_through(vertex));
+            }
+        }
+
+        None
+    }
+
+    fn cycle_through(&self, vertex: usize) -> Vec<&'a Edge> {
+        let mut cycle = Vec::new();
+        let mut current = vertex;
+        while let Some(edge) = self.edge_to[current] {
+            cycle.push(edge);
+            current = edge.from;
+            if current == vertex {
+                break;
+            }
+        }
+        cycle.reverse();
+        cycle
+    }
+
+    /// Edges of a negative cycle reachable from the source in the order of traversal.
+    pub fn negative_cycle(&self) -> Option<&[&'a Edge]> {
+        self.cycle.as_deref()
+    }
+
+    pub fn has_negative_cycle(&self) -> bool {
+        self.cycle.is_some()
+    }
+
+    /// Edges of the shortest path from the source to `target`. It's empty if there is no path or
+    /// `target` is the source. Paths are meaningless if there is a negative cycle.
+    pub fn path_to(&self, target: usize) -> vec::IntoIter<&'a Edge> {
+        let mut path = Vec::new();
+        let mut current = self.edge_to.get(target).and_then(|x| *x);
+        // the limit guards from walking around a negative cycle.
+        while let Some(edge) = current.filter(|_| path.len() < self.edge_to.len()) {
+            path.push(edge);
+            current = self.edge_to[edge.from];
+        }
+        path.reverse();
+        path.into_iter()
+    }
+
+    /// Distance from the source to `target`. It's infinite if there is no path. Distances are
+    /// meaningless if there is a negative cycle.
+    pub fn dist_to(&self, target: usize) -> f32 {
+        self.dist_to.get(target).copied().unwrap_or(INFINITY)
+    }
+
+    pub fn has_path_to(&self, target: usize) -> bool {
+        self.dist_to(target) < INFINITY
+    }
+
+    /// Renders `graph` which the paths are found in to DOT highlighting the negative cycle if there
+    /// is one. Otherwise it highlights the path to `target` or the whole tree of shortest paths if
+    /// there is no target.
+    pub fn to_dot(&self, graph: &Digraph, target: Option<usize>) -> String {
+        let path = match (&self.cycle, target) {
+            (Some(cycle), _) => cycle.clone(),
+            (None, Some(target)) => self.path_to(target).collect(),
+            (None, None) => self.edge_to.iter().filter_map(|x| *x).collect(),
+        };
+        graph.to_dot_with_paths(&[path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::digraph::build;
+    use crate::graph::digraph::pairs;
+    use crate::graph::dot::load_asset;
+
+    #[test]
+    fn negative_weights() {
+        let graph = load_asset("algs4/tinyEWDn.dot").digraph().unwrap();
+        let shortest_path = BellmanFord::new(&graph, 0).unwrap();
+        assert!(!shortest_path.has_negative_cycle());
+        assert_eq!(pairs(shortest_path.path_to(4)), vec![(0, 2), (2, 7), (7, 3), (3, 6), (6, 4)]);
+        assert!((shortest_path.dist_to(4) - 0.26).abs() < 1e-5);
+        assert!((shortest_path.dist_to(1) - 0.93).abs() < 1e-5);
+        assert_eq!(shortest_path.dist_to(0), 0.);
+        assert_eq!(shortest_path.path_to(0).next(), None);
+    }
+
+    #[test]
+    fn unreachable() {
+        let graph = build(3, &[(1, 0, -1.), (0, 2, 1.)]);
+        let shortest_path = BellmanFord::new(&graph, 0).unwrap();
+        assert!(!shortest_path.has_path_to(1));
+        assert_eq!(shortest_path.path_to(1).next(), None);
+        assert!(shortest_path.has_path_to(2));
+        assert!(BellmanFord::new(&graph, 3).is_err());
+    }
+
+    #[test]
+    fn negative_cycle() {
+        let graph = load_asset("algs4/tinyEWDnc.dot").digraph().unwrap();
+        let shortest_path = BellmanFord::new(&graph, 0).unwrap();
+        let cycle = shortest_path.negative_cycle().unwrap();
+        assert_eq!(cycle.len(), 2);
+        assert!(cycle.iter().all(|x| (x.from, x.to) == (4, 5) || (x.from, x.to) == (5, 4)));
+        assert_eq!(cycle[0].to, cycle[1].from);
+        assert!(shortest_path.to_dot(&graph, None).contains("\t5 -> 4 [label=\"-0.66\", color=\"green\"];\n"));
+
+        // a negative cycle which is not reachable from the source doesn't matter.
+        assert!(!BellmanFord::new(&build(4, &[(0, 1, 1.), (2, 3, -1.), (3, 2, -1.)]), 0).unwrap().has_negative_cycle());
+    }
+
+    #[test]
+    fn arbitrage() {
+        let currencies = ["USD", "EUR", "GBP", "CHF", "CAD"];
+        let rates: [[f32; 5]; 5] = [
+            [1., 0.741, 0.657, 1.061, 1.005],
+            [1.349, 1., 0.888, 1.433, 1.366],
+            [1.521, 1.126, 1., 1.614, 1.538],
+            [0.942, 0.698, 0.619, 1., 0.953],
+            [0.995, 0.732, 0.650, 1.049, 1.],
+        ];
+        let mut graph = Digraph::new(currencies.len());
+        for (from, rates) in rates.iter().enumerate() {
+            for (to, rate) in rates.iter().enumerate().filter(|(to, _)| *to != from) {
+                graph.add(Edge{ from, to, weight: -rate.ln() });
+            }
+        }
+
+        let shortest_path = BellmanFord::new(&graph, 0).unwrap();
+        let cycle = shortest_path.negative_cycle().unwrap();
+        let stake = cycle.iter().fold(1000., |stake, x| stake * rates[x.from][x.to]);
+        assert!(stake > 1000.);
+        assert!(cycle.windows(2).all(|x| x[0].to == x[1].from));
+        assert_eq!(cycle.last().unwrap().to, cycle[0].from);
+    }
+}
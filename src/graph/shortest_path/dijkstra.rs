@@ -1,14 +1,18 @@
+use std::f32::INFINITY;
+use std::vec;
+
 use crate::graph::Digraph;
 use crate::graph::Edge;
 use crate::container::IndexedHeap;
-use std::f32::INFINITY;
+use super::PathError;
 
-pub struct EdgeIter<'a> {
-    edge_to: &'a Vec<Option<&'a Edge>>,
+/// Provides edges of a path in reverse order.
+struct EdgeIter<'s, 'a> {
+    edge_to: &'s [Option<&'a Edge>],
     current: Option<&'a Edge>,
 }
 
-impl<'a> Iterator for EdgeIter<'a> {
+impl<'s, 'a> Iterator for EdgeIter<'s, 'a> {
     type Item = &'a Edge;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -24,7 +28,15 @@ pub struct Dijkstra<'a>{
 }
 
 impl<'a> Dijkstra<'a> {
-    fn new(graph: &'a Digraph, source: usize) -> Result<Self, String> {
+    /// Finds shortest paths from `source` to every vertex. Weights must be non-negative.
+    pub fn new(graph: &'a Digraph, source: usize) -> Result<Self, PathError> {
+        if source >= graph.len() {
+            return Err(PathError::InvalidSource(source));
+        }
+        if let Some(edge) = (0 .. graph.len()).flat_map(|x| graph.adj(x)).find(|x| x.weight < 0.) {
+            return Err(PathError::NegativeWeight(edge.from, edge.to));
+        }
+
         let mut edge_to = vec![None; graph.len()];
         let mut dist_to = vec![INFINITY; graph.len()];
         dist_to[source] = 0.;
@@ -57,12 +69,39 @@ impl<'a> Dijkstra<'a> {
         Ok(Self { edge_to, dist_to })
     }
     
-    fn path_to(&self, target: usize) -> EdgeIter {
+    fn edges_to(&self, target: usize) -> EdgeIter<'_, 'a> {
         EdgeIter{
             edge_to: &self.edge_to,
             current: self.edge_to.get(target).and_then(|x| *x),
         }
     }
+
+    /// Edges of the shortest path from the source to `target`. It's empty if there is no path or
+    /// `target` is the source.
+    pub fn path_to(&self, target: usize) -> vec::IntoIter<&'a Edge> {
+        let mut path: Vec<&'a Edge> = self.edges_to(target).collect();
+        path.reverse();
+        path.into_iter()
+    }
+
+    /// Distance from the source to `target`. It's infinite if there is no path.
+    pub fn dist_to(&self, target: usize) -> f32 {
+        self.dist_to.get(target).copied().unwrap_or(INFINITY)
+    }
+
+    pub fn has_path_to(&self, target: usize) -> bool {
+        self.dist_to(target) < INFINITY
+    }
+
+    /// Renders `graph` which the paths are found in to DOT highlighting the path to `target` or
+    /// the whole tree of shortest paths if there is no target.
+    pub fn to_dot(&self, graph: &Digraph, target: Option<usize>) -> String {
+        let path = match target {
+            Some(target) => self.edges_to(target).collect(),
+            None => self.edge_to.iter().filter_map(|x| *x).collect(),
+        };
+        graph.to_dot_with_paths(&[path])
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +122,12 @@ mod tests {
         let expected_edge_to_from = [None, Some(2), Some(0)];
         assert_eq!(shortest_path.dist_to, expected_dist_to);
         assert_eq!(shortest_path.edge_to.iter().map(|x| x.map(|y| y.from)).collect::<Vec<_>>(), expected_edge_to_from);
+
+        let dot = shortest_path.to_dot(&di, Some(2));
+        assert!(dot.contains("\t0 -> 1 [label=\"0.9\"];\n"));
+        assert!(dot.contains("\t0 -> 2 [label=\"0.1\", color=\"green\"];\n"));
+        assert!(dot.contains("\t2 -> 1 [label=\"0.3\"];\n"));
+        assert!(shortest_path.to_dot(&di, None).contains("\t2 -> 1 [label=\"0.3\", color=\"green\"];\n"));
     }
 
     #[test]
@@ -96,6 +141,42 @@ mod tests {
         assert!(shortest_path.is_ok(), shortest_path.err().unwrap());
     }
 
+    #[test]
+    fn forward_path() {
+        let mut di = Digraph::new(4);
+        di.add(Edge{from: 3, to: 2, weight: 0.1});
+        di.add(Edge{from: 2, to: 1, weight: 0.2});
+        di.add(Edge{from: 1, to: 0, weight: 0.3});
+        di.add(Edge{from: 3, to: 0, weight: 0.9});
+        let shortest_path = Dijkstra::new(&di, 3).unwrap();
+        let path: Vec<_> = shortest_path.path_to(0).map(|x| (x.from, x.to)).collect();
+        assert_eq!(path, vec![(3, 2), (2, 1), (1, 0)]);
+        assert!((shortest_path.dist_to(0) - 0.6).abs() < 1e-6);
+        assert_eq!(shortest_path.dist_to(3), 0.);
+        assert!(shortest_path.has_path_to(3));
+        assert_eq!(shortest_path.path_to(3).next(), None);
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut di = Digraph::new(3);
+        di.add(Edge{from: 1, to: 0, weight: 0.1});
+        let shortest_path = Dijkstra::new(&di, 0).unwrap();
+        assert!(!shortest_path.has_path_to(1));
+        assert_eq!(shortest_path.dist_to(1), INFINITY);
+        assert_eq!(shortest_path.path_to(1).next(), None);
+        assert!(!shortest_path.has_path_to(5));
+    }
+
+    #[test]
+    fn errors() {
+        let mut di = Digraph::new(2);
+        assert_eq!(Dijkstra::new(&di, 2).err(), Some(PathError::InvalidSource(2)));
+        di.add(Edge{from: 1, to: 0, weight: -0.1});
+        assert_eq!(Dijkstra::new(&di, 0).err(), Some(PathError::NegativeWeight(1, 0)));
+        assert_eq!(PathError::NegativeWeight(1, 0).to_string(), "edge 1 -> 0 has a negative weight");
+    }
+
     mod skip_edge {
         use super::*;
         use std::mem;
@@ -121,7 +202,7 @@ mod tests {
                     extend_lifetime(di.as_ref())
                 };
 
-                let sp = Dijkstra::new(&di_static, 0)?;
+                let sp = Dijkstra::new(&di_static, 0).map_err(|e| e.to_string())?;
                 Ok(Self { di, sp, source })
             }
 
@@ -129,22 +210,20 @@ mod tests {
                 let mut di = Digraph::new(self.di.len());
                 let edge_iters = (0 .. self.di.len()).filter(|x| x != &self.source).map(|x| self.di.adj(x));
                 edge_iters.flatten().for_each(|x| di.add(Edge{from: x.to, to: x.from, weight: x.weight}));
-                let reverse_sp = Dijkstra::new(&di, target)?;
+                let reverse_sp = Dijkstra::new(&di, target).map_err(|e| e.to_string())?;
                 let alternative_dist = self.sp.dist_to[from] + reverse_sp.dist_to[to];
                 let dist = self.sp.dist_to[target];
 
                 if alternative_dist < dist {
                     let mut edges_from = self.sp.path_to(from).map(|x| x.clone()).collect::<Vec<_>>();
-                    edges_from.reverse();
-                    let mut edges_to = reverse_sp.path_to(to).map(|x| x.clone()).collect::<Vec<_>>();
+                    // edges of the reverse graph go from the target.
+                    let mut edges_to = reverse_sp.path_to(to).rev().map(|x| x.clone()).collect::<Vec<_>>();
                     edges_from.push(Edge{from, to, weight: 0.});
                     edges_from.append(&mut edges_to);
                     return Ok(edges_from);
                 }
 
-                let mut edges = self.sp.path_to(target).map(|x| x.clone()).collect::<Vec<_>>();
-                edges.reverse();
-                Ok(edges)
+                Ok(self.sp.path_to(target).map(|x| x.clone()).collect())
             }
         }
 
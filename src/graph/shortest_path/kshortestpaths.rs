@@ -1,14 +1,18 @@
 use std::cmp::Ordering;
+use std::f32::INFINITY;
+use std::vec;
 
 use crate::graph::Edge;
 use crate::random::xorshift_rng as random;
 use crate::graph::Digraph;
 use crate::container::Heap;
+use super::PathError;
 
 #[derive(Clone)]
 struct EdgeLink<'a> {
     edge: &'a Edge,
-    previous_vertex_edge: usize,
+    /// Index of the previous link in the paths to `edge.from`. The first edge of a path has none.
+    previous_vertex_edge: Option<usize>,
     distance: f32,
 }
 
@@ -24,35 +28,37 @@ impl<'a> PartialOrd for EdgeLink<'a> {
     }
 }
 
-struct KShortestPathsIter<'a> {
-    current: Option<&'a EdgeLink<'a>>,
-    edge_to: &'a Vec<Vec<EdgeLink<'a>>>,
-    source: usize,
-    path_order: usize,
+struct KShortestPathsIter<'s, 'a> {
+    current: Option<&'s EdgeLink<'a>>,
+    edge_to: &'s [Vec<EdgeLink<'a>>],
 }
 
 /// Provides edges in reverse order but without copying.
-impl<'a> Iterator for KShortestPathsIter<'a> {
+impl<'s, 'a> Iterator for KShortestPathsIter<'s, 'a> {
     type Item = &'a Edge;
 
     fn next(&mut self) -> Option<Self::Item> {
         let current = self.current.take();
-        self.current = current.filter(|x| x.edge.from > 0)
-            .map(|x| &self.edge_to[x.edge.from][x.previous_vertex_edge]);
+        self.current = current.and_then(|x| Some(&self.edge_to[x.edge.from][x.previous_vertex_edge?]));
         current.map(|x| x.edge)
     }
 }
 
 pub struct KShortestPaths<'a> {
-    source: usize,
     edge_to: Vec<Vec<EdgeLink<'a>>>, // mean to be sorted
 }
 
 impl<'a> KShortestPaths<'a> {
-    fn new(graph: &'a Digraph, max_paths: usize, source: usize) -> Self {
+    /// Finds up to `max_paths` shortest paths from `source` to every vertex. Paths may go
+    /// through a vertex several times, paths to the source itself are cycles.
+    pub fn new(graph: &'a Digraph, max_paths: usize, source: usize) -> Result<Self, PathError> {
+        if source >= graph.len() {
+            return Err(PathError::InvalidSource(source));
+        }
+
         let mut edge_to = vec![Vec::new(); graph.len()];
         let mut heap = Heap::min();
-        graph.adj(source).for_each(|x| heap.push(EdgeLink{ edge: x, previous_vertex_edge: 0, distance: x.weight }));
+        graph.adj(source).for_each(|x| heap.push(EdgeLink{ edge: x, previous_vertex_edge: None, distance: x.weight }));
         while let Some(edge_link) = heap.pop() {
             let vertex = edge_link.edge.to;
             if edge_to[vertex].len() >= max_paths {
@@ -63,7 +69,7 @@ impl<'a> KShortestPaths<'a> {
             graph.adj(vertex).for_each(|next_edge| {
                 let next_edge_link = EdgeLink {
                     edge: next_edge,
-                    previous_vertex_edge: edge_to[vertex].len() - 1,
+                    previous_vertex_edge: Some(edge_to[vertex].len() - 1),
                     distance: edge_link.distance + next_edge.weight,
                 };
 
@@ -71,51 +77,73 @@ impl<'a> KShortestPaths<'a> {
             });
         }
 
-        Self { edge_to, source }
+        Ok(Self { edge_to })
     }
 
-    fn path_to(&self, vertex: usize, path_order: usize) -> KShortestPathsIter {
+    fn edges_to(&self, vertex: usize, path_order: usize) -> KShortestPathsIter<'_, 'a> {
         KShortestPathsIter {
             edge_to: &self.edge_to,
             current: self.edge_to.get(vertex).and_then(|x| x.get(path_order)),
-            source: self.source,
-            path_order,
         }
     }
+
+    /// Edges of the path to `vertex` which is `path_order`-th by distance starting with zero.
+    /// It's empty if there is no such path.
+    pub fn path_to(&self, vertex: usize, path_order: usize) -> vec::IntoIter<&'a Edge> {
+        let mut path: Vec<&'a Edge> = self.edges_to(vertex, path_order).collect();
+        path.reverse();
+        path.into_iter()
+    }
+
+    /// Number of found paths to `vertex`.
+    pub fn count(&self, vertex: usize) -> usize {
+        self.edge_to.get(vertex).map_or(0, Vec::len)
+    }
+
+    /// Distance of the `path_order`-th path to `vertex`. It's infinite if there is no such path.
+    pub fn dist_to(&self, vertex: usize, path_order: usize) -> f32 {
+        self.edge_to.get(vertex).and_then(|x| x.get(path_order)).map_or(INFINITY, |x| x.distance)
+    }
+
+    pub fn has_path_to(&self, vertex: usize, path_order: usize) -> bool {
+        path_order < self.count(vertex)
+    }
+
+    /// Renders `graph` which the paths are found in to DOT highlighting every found path to
+    /// `vertex` by its own color.
+    pub fn to_dot(&self, graph: &Digraph, vertex: usize) -> String {
+        let paths: Vec<Vec<&Edge>> = (0 .. self.count(vertex)).map(|x| self.edges_to(vertex, x).collect()).collect();
+        graph.to_dot_with_paths(&paths)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::graph::dot::load_asset;
 
     #[test]
     fn different_paths() {
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.1},
-            Edge{from: 1, to: 2, weight: 0.1},
-            Edge{from: 2, to: 4, weight: 0.1},
-
-            Edge{from: 0, to: 3, weight: 0.2},
-            Edge{from: 3, to: 4, weight: 0.2},
-            Edge{from: 4, to: 5, weight: 0.1},
-        ];
-
-        let mut di = Digraph::new(6);
-        edges.into_iter().for_each(|x| di.add(x));
-        let k_shortest_path = KShortestPaths::new(&di, 2, 0);
+        let di = load_asset("different_paths.dot").digraph().unwrap();
+        let k_shortest_path = KShortestPaths::new(&di, 2, 0).unwrap();
 
         let mut path51 = k_shortest_path.path_to(5, 1);
-        assert_eq!(path51.next(), Some(&Edge{from: 4, to: 5, weight: 0.1}));
-        assert_eq!(path51.next(), Some(&Edge{from: 3, to: 4, weight: 0.2}));
         assert_eq!(path51.next(), Some(&Edge{from: 0, to: 3, weight: 0.2}));
+        assert_eq!(path51.next(), Some(&Edge{from: 3, to: 4, weight: 0.2}));
+        assert_eq!(path51.next(), Some(&Edge{from: 4, to: 5, weight: 0.1}));
         assert_eq!(path51.next(), None);
 
         let mut path50 = k_shortest_path.path_to(5, 0);
-        assert_eq!(path50.next(), Some(&Edge{from: 4, to: 5, weight: 0.1}));
-        assert_eq!(path50.next(), Some(&Edge{from: 2, to: 4, weight: 0.1}));
-        assert_eq!(path50.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
         assert_eq!(path50.next(), Some(&Edge{from: 0, to: 1, weight: 0.1}));
+        assert_eq!(path50.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
+        assert_eq!(path50.next(), Some(&Edge{from: 2, to: 4, weight: 0.1}));
+        assert_eq!(path50.next(), Some(&Edge{from: 4, to: 5, weight: 0.1}));
         assert_eq!(path50.next(), None);
+
+        let dot = k_shortest_path.to_dot(&di, 5);
+        assert!(dot.contains("\t0 -> 1 [label=\"0.1\", color=\"green\"];\n"));
+        assert!(dot.contains("\t0 -> 3 [label=\"0.2\", color=\"blue\"];\n"));
+        assert!(dot.contains("\t4 -> 5 [label=\"0.1\", color=\"green:blue\"];\n"));
     }
 
     #[test]
@@ -136,7 +164,7 @@ mod tests {
 
         let mut di = Digraph::new(4);
         edges.into_iter().for_each(|x| di.add(x));
-        let k_shortest_path = KShortestPaths::new(&di, 2, 0);
+        let k_shortest_path = KShortestPaths::new(&di, 2, 0).unwrap();
         assert_eq!(k_shortest_path.edge_to[3].len(), 2);
         assert_eq!(k_shortest_path.edge_to[3][0].distance, 0.3);
         assert_eq!(k_shortest_path.edge_to[3][1].distance, 0.4);
@@ -151,10 +179,31 @@ mod tests {
 
         assert_eq!(k_shortest_path.path_to(3, 3).next(), None);
 
+        assert_eq!(k_shortest_path.count(3), 2);
+        assert!(k_shortest_path.has_path_to(3, 1));
+        assert!(!k_shortest_path.has_path_to(3, 2));
+        assert_eq!(k_shortest_path.dist_to(3, 1), 0.4);
+        assert_eq!(k_shortest_path.dist_to(3, 2), INFINITY);
+
         let mut path31 = k_shortest_path.path_to(3, 1);
-        assert_eq!(path31.next(), Some(&Edge{from: 2, to: 3, weight: 0.2}));
-        assert_eq!(path31.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
         assert_eq!(path31.next(), Some(&Edge{from: 0, to: 1, weight: 0.1}));
+        assert_eq!(path31.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
+        assert_eq!(path31.next(), Some(&Edge{from: 2, to: 3, weight: 0.2}));
         assert_eq!(path31.next(), None);
     }
+
+    #[test]
+    fn through_source() {
+        let mut di = Digraph::new(3);
+        di.add(Edge{from: 1, to: 0, weight: 0.1});
+        di.add(Edge{from: 1, to: 2, weight: 0.5});
+        di.add(Edge{from: 0, to: 1, weight: 0.1});
+        let k_shortest_path = KShortestPaths::new(&di, 2, 1).unwrap();
+        let path = |vertex, order| k_shortest_path.path_to(vertex, order).map(|x| (x.from, x.to)).collect::<Vec<_>>();
+        assert_eq!(path(2, 0), vec![(1, 2)]);
+        // the second path goes through the source again.
+        assert_eq!(path(2, 1), vec![(1, 0), (0, 1), (1, 2)]);
+        assert_eq!(path(1, 0), vec![(1, 0), (0, 1)]);
+        assert!(KShortestPaths::new(&di, 2, 3).is_err());
+    }
 }
@@ -1,7 +1,37 @@
+use std::fmt;
+
+mod acyclic;
+mod bellman_ford;
 mod dijkstra;
 mod monotonic;
 mod kshortestpaths;
 
+pub use acyclic::AcyclicPaths;
+pub use bellman_ford::BellmanFord;
 pub use dijkstra::Dijkstra;
 pub use monotonic::Monotonic;
 pub use kshortestpaths::KShortestPaths;
+
+#[derive(Debug, PartialEq)]
+pub enum PathError {
+    /// The source is not a vertex of the graph.
+    InvalidSource(usize),
+    /// An edge `from -> to` has a negative weight which Dijkstra's algorithm can't handle. Use
+    /// `BellmanFord` for such graphs.
+    NegativeWeight(usize, usize),
+    /// Vertices of a cycle in a graph which must be acyclic.
+    Cycle(Vec<usize>),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::InvalidSource(source) => write!(f, "source {} is not a vertex of the graph", source),
+            PathError::NegativeWeight(from, to) => write!(f, "edge {} -> {} has a negative weight", from, to),
+            PathError::Cycle(vertices) => {
+                let vertices: Vec<String> = vertices.iter().chain(vertices.first()).map(usize::to_string).collect();
+                write!(f, "graph has a cycle {}", vertices.join(" -> "))
+            },
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use std::f32::INFINITY;
 use std::cmp::Ordering;
+use std::vec;
 
 use crate::container::Heap;
 use crate::graph::Digraph;
 use crate::graph::Edge;
 use crate::utils::quicksort;
+use super::PathError;
 
 #[derive(Clone)]
 enum EdgeReference<'a> {
@@ -36,15 +38,17 @@ impl<'a> PartialOrd for EdgeLink<'a> {
 type EdgeLinks<'a> = Vec<Option<EdgeLink<'a>>>;
 
 pub struct Monotonic<'a> {
+    source: usize,
     edge_to: EdgeLinks<'a>,
 }
 
-struct EdgeLinkIter<'a>{
-    cursor: Option<&'a EdgeLink<'a>>,
-    edge_to: &'a EdgeLinks<'a>,
+/// Provides edges of a path in reverse order.
+struct EdgeLinkIter<'s, 'a>{
+    cursor: Option<&'s EdgeLink<'a>>,
+    edge_to: &'s [Option<EdgeLink<'a>>],
 }
 
-impl<'a> Iterator for EdgeLinkIter<'a> {
+impl<'s, 'a> Iterator for EdgeLinkIter<'s, 'a> {
     type Item = &'a Edge;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -60,15 +64,21 @@ impl<'a> Iterator for EdgeLinkIter<'a> {
 }
 
 impl<'a> Monotonic<'a> {
-    fn new(graph: &'a Digraph, ascending: bool) -> Self {
+    /// Finds shortest paths from `source` which weights of edges are ascending or descending along.
+    pub fn new(graph: &'a Digraph, source: usize, ascending: bool) -> Result<Self, PathError> {
+        if source >= graph.len() {
+            return Err(PathError::InvalidSource(source));
+        }
+
         let comp = if ascending { |x, y| x < y } else { |x, y| x > y };
         let mut heap = Heap::min();
         let mut visited_edges_counters = vec![0usize; graph.len()];
         let mut edge_to: EdgeLinks<'a> = vec![None; graph.len()];
-        graph.adj(0).for_each(|x| heap.push(EdgeLink{ edge: x, previous: None, distance: x.weight }));
+        graph.adj(source).for_each(|x| heap.push(EdgeLink{ edge: x, previous: None, distance: x.weight }));
         while let Some(edge_link) = heap.pop() {
             let vertex = edge_link.edge.to;
-            if edge_to[vertex].as_ref().map_or(INFINITY, |x| x.distance) > edge_link.distance {
+            // a path back to the source is never shorter than the empty one.
+            if vertex != source && edge_to[vertex].as_ref().map_or(INFINITY, |x| x.distance) > edge_link.distance {
                 edge_to[vertex] = Some(edge_link.clone());
             }
 
@@ -133,21 +143,54 @@ impl<'a> Monotonic<'a> {
             });
         }
 
-        Self { edge_to }
+        Ok(Self { source, edge_to })
     }
 
-    fn path_to(&self, vertex: usize) -> EdgeLinkIter {
+    fn edges_to(&self, vertex: usize) -> EdgeLinkIter<'_, 'a> {
         EdgeLinkIter{
-            cursor: self.edge_to[vertex].as_ref(),
+            cursor: self.edge_to.get(vertex).and_then(Option::as_ref),
             edge_to: &self.edge_to,
         }
     }
+
+    /// Edges of the shortest monotonic path from the source to `vertex`. It's empty if there is no
+    /// path or `vertex` is the source.
+    pub fn path_to(&self, vertex: usize) -> vec::IntoIter<&'a Edge> {
+        let mut path: Vec<&'a Edge> = self.edges_to(vertex).collect();
+        path.reverse();
+        path.into_iter()
+    }
+
+    /// Distance from the source to `vertex`. It's infinite if there is no monotonic path.
+    pub fn dist_to(&self, vertex: usize) -> f32 {
+        match self.edge_to.get(vertex) {
+            _ if vertex == self.source => 0.,
+            Some(Some(link)) => link.distance,
+            _ => INFINITY,
+        }
+    }
+
+    pub fn has_path_to(&self, vertex: usize) -> bool {
+        self.dist_to(vertex) < INFINITY
+    }
+
+    /// Renders `graph` which the paths are found in to DOT highlighting the path to `vertex`.
+    pub fn to_dot(&self, graph: &Digraph, vertex: usize) -> String {
+        graph.to_dot_with_paths(&[self.edges_to(vertex).collect()])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::mem;
+    use crate::graph::dot::load_asset;
+
+    /// Edges of a file of `assets` in the order of the file.
+    fn asset_edges(path: &str) -> Vec<Edge> {
+        let dot = load_asset(path);
+        dot.edges().iter().map(|x| Edge { from: x.from, to: x.to, weight: x.attributes["label"].parse().unwrap() }).collect()
+    }
 
 
     struct Test {
@@ -162,11 +205,13 @@ mod tests {
         fn run(self) {
             let mut graph = Digraph::new(self.edges.len());
             self.edges.into_iter().for_each(|x| graph.add(x));
-            let shortest_path = Monotonic::new(&graph, self.ascending);
+            let shortest_path = Monotonic::new(&graph, 0, self.ascending).unwrap();
             match self.expected_edge {
                 Some(ref expected) => {
                     let actual = shortest_path.edge_to[self.target].as_ref().map(|x| x.edge);
                     assert_eq!(actual, Some(expected));
+                    let highlighted = format!("\t{} -> {} [label=\"{}\", color=\"green\"];\n", expected.from, expected.to, expected.weight);
+                    assert!(shortest_path.to_dot(&graph, self.target).contains(&highlighted));
                 },
                 None => assert!(shortest_path.edge_to[self.target].is_none()),
             }
@@ -180,15 +225,7 @@ mod tests {
     #[test]
     fn shortest_wrong_ascending() {
         const TARGET: usize = 5;
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.3},
-            Edge{from: 1, to: 2, weight: 0.4},
-            Edge{from: 2, to: TARGET, weight: 0.5},
-
-            Edge{from: 0, to: 3, weight: 0.1},
-            Edge{from: 3, to: 4, weight: 0.2},
-            Edge{from: 4, to: TARGET, weight: 0.1},
-        ];
+        let edges = asset_edges("shortest_wrong_ascending.dot");
         Test{
             target: TARGET,
             expected_edge: Some(edges[2].clone()),
@@ -201,15 +238,7 @@ mod tests {
     #[test]
     fn shortest_right_ascending() {
         const TARGET: usize = 5;
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.3},
-            Edge{from: 1, to: 2, weight: 0.4},
-            Edge{from: 2, to: TARGET, weight: 0.5},
-
-            Edge{from: 0, to: 3, weight: 0.1},
-            Edge{from: 3, to: 4, weight: 0.2},
-            Edge{from: 4, to: TARGET, weight: 0.3},
-        ];
+        let edges = asset_edges("shortest_right_ascending.dot");
         Test{
             target: TARGET,
             expected_edge: Some(edges[5].clone()),
@@ -222,14 +251,7 @@ mod tests {
     #[test]
     fn shortest_right_descending() {
         const TARGET: usize = 4;
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.6},
-            Edge{from: 1, to: 2, weight: 0.3},
-
-            Edge{from: 0, to: 2, weight: 0.3},
-            Edge{from: 2, to: 3, weight: 0.2},
-            Edge{from: 3, to: TARGET, weight: 0.1},
-        ];
+        let edges = asset_edges("shortest_right_descending.dot");
         Test{
             target: TARGET,
             expected_edge: Some(edges[4].clone()),
@@ -242,14 +264,7 @@ mod tests {
     #[test]
     fn alternative_right_descending() {
         const TARGET: usize = 4;
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.6},
-            Edge{from: 1, to: 2, weight: 0.3},
-
-            Edge{from: 0, to: 2, weight: 0.1},
-            Edge{from: 2, to: 3, weight: 0.2},
-            Edge{from: 3, to: TARGET, weight: 0.1},
-        ];
+        let edges = asset_edges("alternative_right_descending.dot");
 
         Test{
             target: TARGET,
@@ -281,64 +296,46 @@ mod tests {
         let third_vertex_distance = [edges[0].weight, edges[4].weight].iter().sum();
         let mut graph = Digraph::new(edges.len());
         edges.into_iter().for_each(|x| graph.add(x));
-        let shortest_path = Monotonic::new(&graph, false);
+        let shortest_path = Monotonic::new(&graph, 0, false).unwrap();
         assert_eq!(shortest_path.edge_to[5].as_ref().map(dist), Some(fifth_vertex_distance));
         assert_eq!(shortest_path.edge_to[4].as_ref().map(dist), Some(third_vertex_distance));
         assert_eq!(shortest_path.edge_to[3].as_ref().map(dist), Some(fourth_vertex_distance));
 
         let mut iter = shortest_path.path_to(5);
-        assert_eq!(iter.next(), Some(&Edge{from: 1, to: 5, weight: 0.4}));
-        assert_eq!(iter.next(), Some(&Edge{from: 2, to: 1, weight: 0.6}));
         assert_eq!(iter.next(), Some(&Edge{from: 0, to: 2, weight: 0.7}));
+        assert_eq!(iter.next(), Some(&Edge{from: 2, to: 1, weight: 0.6}));
+        assert_eq!(iter.next(), Some(&Edge{from: 1, to: 5, weight: 0.4}));
         assert_eq!(iter.next(), None);
     }
 
     #[test]
     fn three_descending_paths_through_one_vertex() {
         // visualize dot ./assets/memory.dot -Tsvg > memory.svg
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.2},
-            Edge{from: 1, to: 2, weight: 0.1},
-
-            Edge{from: 0, to: 1, weight: 0.3},
-            Edge{from: 1, to: 3, weight: 0.2},
-
-            Edge{from: 0, to: 1, weight: 0.4},
-            Edge{from: 1, to: 4, weight: 0.3},
-        ];
+        let edges = asset_edges("memory.dot");
 
         let mut graph = Digraph::new(5);
         edges.into_iter().for_each(|x| graph.add(x));
-        let shortest_path = Monotonic::new(&graph, false);
+        let shortest_path = Monotonic::new(&graph, 0, false).unwrap();
         let mut path4 = shortest_path.path_to(4);
-        assert_eq!(path4.next(), Some(&Edge{from: 1, to: 4, weight: 0.3}));
         assert_eq!(path4.next(), Some(&Edge{from: 0, to: 1, weight: 0.4}));
+        assert_eq!(path4.next(), Some(&Edge{from: 1, to: 4, weight: 0.3}));
         assert_eq!(path4.next(), None);
 
         let mut path3 = shortest_path.path_to(3);
-        assert_eq!(path3.next(), Some(&Edge{from: 1, to: 3, weight: 0.2}));
         assert_eq!(path3.next(), Some(&Edge{from: 0, to: 1, weight: 0.3}));
+        assert_eq!(path3.next(), Some(&Edge{from: 1, to: 3, weight: 0.2}));
         assert_eq!(path3.next(), None);
 
         let mut path2 = shortest_path.path_to(2);
-        assert_eq!(path2.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
         assert_eq!(path2.next(), Some(&Edge{from: 0, to: 1, weight: 0.2}));
+        assert_eq!(path2.next(), Some(&Edge{from: 1, to: 2, weight: 0.1}));
         assert_eq!(path2.next(), None);
     }
 
     #[test]
     fn memory() {
         // visualize dot ./assets/memory.dot -Tsvg > memory.svg
-        let edges = vec![
-            Edge{from: 0, to: 1, weight: 0.2},
-            Edge{from: 1, to: 2, weight: 0.1},
-
-            Edge{from: 0, to: 1, weight: 0.3},
-            Edge{from: 1, to: 3, weight: 0.2},
-
-            Edge{from: 0, to: 1, weight: 0.4},
-            Edge{from: 1, to: 4, weight: 0.3},
-        ];
+        let edges = asset_edges("memory.dot");
 
         let edge_reference_index = EdgeReference::Index(1);
         assert_eq!(mem::size_of_val(&edge_reference_index), 16);
@@ -353,8 +350,26 @@ mod tests {
 
         let mut graph = Digraph::new(5);
         edges.into_iter().for_each(|x| graph.add(x));
-        let shortest_path = Monotonic::new(&graph, false);
-        // without alternative edges which allocated on the heap. Actual size is 32 * 5 bytes
-        assert_eq!(mem::size_of_val(&shortest_path), 24);
+        let shortest_path = Monotonic::new(&graph, 0, false).unwrap();
+        // the source and edge links without alternative edges which allocated on the heap. Actual
+        // size of the links is 32 * 5 bytes
+        assert_eq!(mem::size_of_val(&shortest_path), 32);
+    }
+
+    #[test]
+    fn non_zero_source() {
+        let mut graph = Digraph::new(4);
+        graph.add(Edge{from: 0, to: 1, weight: 0.1});
+        graph.add(Edge{from: 1, to: 2, weight: 0.2});
+        graph.add(Edge{from: 2, to: 1, weight: 0.3});
+        graph.add(Edge{from: 2, to: 3, weight: 0.9});
+        graph.add(Edge{from: 1, to: 3, weight: 0.4});
+        let shortest_path = Monotonic::new(&graph, 2, true).unwrap();
+        assert_eq!(shortest_path.path_to(3).map(|x| (x.from, x.to)).collect::<Vec<_>>(), vec![(2, 1), (1, 3)]);
+        assert!((shortest_path.dist_to(3) - 0.7).abs() < 1e-6);
+        assert_eq!(shortest_path.dist_to(2), 0.);
+        assert!(!shortest_path.has_path_to(0));
+        assert_eq!(shortest_path.path_to(0).next(), None);
+        assert_eq!(Monotonic::new(&graph, 4, true).err(), Some(PathError::InvalidSource(4)));
     }
 }
@@ -14,6 +14,7 @@ mod random;
 mod graph;
 mod trace;
 mod str;
+mod cli;
 #[macro_use]
 mod macros;
 
@@ -48,11 +49,8 @@ impl<T> Exit<T> for Option<T> {
 }
 
 fn main() {
-    //let ret: Vec<u32> = vec![0; 3].into_iter().map(|_| random()).collect();
-    //println!("{:?}", ret);
-    // let filename = args().skip(1).next().or_exit("Point filename");
-    // println!("{}", filename);
-    // let filename = args().skip(1).next().expect("Point filename");
-    // let metadata = fs::File::open(filename).and_then(|x| x.metadata()).expect("Can't read file");
-    // println!("{:o}", metadata.mode())
+    if let Err(e) = cli::run(args().skip(1)) {
+        eprintln!("{}", e);
+        exit(e.code());
+    }
 }
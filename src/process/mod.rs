@@ -1,8 +1,34 @@
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::MetadataExt;
 use std::path;
+use std::str::FromStr;
+use std::time::Duration;
+
+mod net;
+mod open_file;
+mod sampler;
+mod signal;
+mod tree;
+pub use self::net::Address;
+pub use self::net::Connection;
+pub use self::net::Protocol;
+pub use self::net::Socket;
+pub use self::net::SocketState;
+pub use self::net::SocketTable;
+pub use self::open_file::FileKind;
+pub use self::open_file::Holding;
+pub use self::open_file::OpenFile;
+pub use self::sampler::ProcessSampler;
+pub use self::sampler::Sample;
+pub use self::signal::Signal;
+pub use self::tree::ProcessTree;
+pub use self::tree::Totals;
 
 
 fn is_user_folder(dir: &fs::DirEntry, uid: u32) -> bool {
@@ -12,84 +38,456 @@ fn is_user_folder(dir: &fs::DirEntry, uid: u32) -> bool {
     }
 }
 
-#[derive(Debug)]
+/// Scheduling state, see proc(5).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Running, // R
+    Sleeping, // S
+    DiskSleep, // D
+    Zombie, // Z
+    Stopped, // T
+    TracingStop, // t
+    Dead, // X
+    Idle, // I
+    Parked, // P
+    Unknown(char),
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State::Unknown('?')
+    }
+}
+
+impl From<char> for State {
+    fn from(code: char) -> Self {
+        match code {
+            'R' => State::Running,
+            'S' => State::Sleeping,
+            'D' => State::DiskSleep,
+            'Z' => State::Zombie,
+            'T' => State::Stopped,
+            't' => State::TracingStop,
+            'X' | 'x' => State::Dead,
+            'I' => State::Idle,
+            'P' => State::Parked,
+            x => State::Unknown(x),
+        }
+    }
+}
+
+/// User or group ids of a process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Ids {
+    pub real: u32,
+    pub effective: u32,
+    pub saved: u32,
+    pub filesystem: u32,
+}
+
+#[derive(Debug, Default)]
 pub struct Process {
    pub name: String,
    pub pid: u32,
    pub ppid: u32,
+   pub state: State,
+   pub uid: Ids,
+   pub gid: Ids,
+   pub threads: u32,
+   /// Resident set size in bytes. Kernel threads have no memory of their own.
+   pub vm_rss: Option<u64>,
+   /// Virtual memory size in bytes.
+   pub vm_size: Option<u64>,
+   /// Time since boot the process started at.
+   pub start_time: Duration,
+   /// Time spent in user mode.
+   pub utime: Duration,
+   /// Time spent in kernel mode.
+   pub stime: Duration,
+   /// Arguments the process is started with. Empty for kernel threads and zombies.
+   pub cmdline: Vec<OsString>,
+   /// These are `None` if the process belongs to another user or it is a kernel thread.
+   pub exe: Option<path::PathBuf>,
+   pub cwd: Option<path::PathBuf>,
+   pub environ: Option<Vec<(OsString, OsString)>>,
+   pub voluntary_switches: u64,
+   pub involuntary_switches: u64,
+   dir: path::PathBuf,
+}
+
+fn to_int<T: FromStr>(val: &str) -> Result<T, ProcError> {
+    val.trim().parse().map_err(|_| ProcError::IncorrectData(format!("Can't convert `{}` to int", val)))
+}
+
+fn to_ids(val: &str) -> Result<Ids, ProcError> {
+    let ids = val.split_whitespace().map(to_int).collect::<Result<Vec<u32>, _>>()?;
+    match ids[..] {
+        [real, effective, saved, filesystem] => Ok(Ids { real, effective, saved, filesystem }),
+        _ => Err(ProcError::IncorrectData(format!("Can't convert `{}` to ids", val))),
+    }
 }
 
-fn to_int(val: &str) -> u32 {
-    val.trim().parse().expect(&format!("Can't convert `{}` to int", val))
+/// Converts a value like `1234 kB` to bytes.
+fn to_bytes(val: &str) -> Result<u64, ProcError> {
+    let val = val.trim();
+    let number = val.strip_suffix("kB").unwrap_or(val);
+    Ok(to_int::<u64>(number)? * 1024)
 }
 
-fn fill_process(p: &mut Process, status_file_line: &str) {
-    let mut line_parts = status_file_line.split(':');
+fn fill_process(p: &mut Process, status_file_line: &str) -> Result<(), ProcError> {
+    let mut line_parts = status_file_line.splitn(2, ':');
     if let Some(before_colon) = line_parts.next() {
+        let value = match line_parts.next() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
         match before_colon {
-            "Name" => p.name = line_parts.next().unwrap().trim().to_owned(),
-            "Pid" => p.pid = to_int(line_parts.next().unwrap()),
-            "PPid" => p.ppid = to_int(line_parts.next().unwrap()),
-            _ => return,
+            "Name" => p.name = value.trim().to_owned(),
+            "Pid" => p.pid = to_int(value)?,
+            "PPid" => p.ppid = to_int(value)?,
+            "Uid" => p.uid = to_ids(value)?,
+            "Gid" => p.gid = to_ids(value)?,
+            "Threads" => p.threads = to_int(value)?,
+            "VmRSS" => p.vm_rss = Some(to_bytes(value)?),
+            "VmSize" => p.vm_size = Some(to_bytes(value)?),
+            "voluntary_ctxt_switches" => p.voluntary_switches = to_int(value)?,
+            "nonvoluntary_ctxt_switches" => p.involuntary_switches = to_int(value)?,
+            _ => (),
         }
     }
+    Ok(())
+}
+
+fn ticks_per_second() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        x if x > 0 => x as u64,
+        _ => 100,
+    }
+}
+
+fn clock_ticks(ticks: &str) -> Result<Duration, ProcError> {
+    let ticks_per_second = ticks_per_second();
+    let ticks: u64 = to_int(ticks)?;
+    Ok(Duration::from_secs(ticks / ticks_per_second)
+        + Duration::from_nanos(ticks % ticks_per_second * 1_000_000_000 / ticks_per_second))
+}
+
+/// Fills the fields from `/proc/<pid>/stat`. The name is skipped as it may contain spaces and
+/// parentheses.
+fn fill_stat(p: &mut Process, stat: &str) -> Result<(), ProcError> {
+    let incorrect = || ProcError::IncorrectData(format!("Can't parse stat `{}`", stat.trim_end()));
+    let after_name = &stat[stat.rfind(')').ok_or_else(incorrect)? + 1 ..];
+
+    // fields are counted from the state which is the third one.
+    let fields: Vec<&str> = after_name.split_whitespace().collect();
+    if fields.len() < 20 {
+        return Err(incorrect());
+    }
+
+    p.state = State::from(fields[0].chars().next().unwrap_or('?'));
+    p.utime = clock_ticks(fields[11])?;
+    p.stime = clock_ticks(fields[12])?;
+    p.start_time = clock_ticks(fields[19])?;
+    Ok(())
+}
+
+fn split_nul(data: Vec<u8>) -> Vec<OsString> {
+    data.split(|x| *x == 0).filter(|x| !x.is_empty()).map(|x| OsString::from_vec(x.to_vec())).collect()
+}
+
+fn to_environ(data: Vec<u8>) -> Vec<(OsString, OsString)> {
+    split_nul(data).into_iter().map(|x| {
+        let mut bytes = x.into_vec();
+        match bytes.iter().position(|x| *x == b'=') {
+            Some(pos) => {
+                let value = bytes.split_off(pos + 1);
+                bytes.pop();
+                (OsString::from_vec(bytes), OsString::from_vec(value))
+            },
+            None => (OsString::from_vec(bytes), OsString::new()),
+        }
+    }).collect()
 }
 
 impl Process {
-    fn new(status_file_path: &str) -> Self {
-        let file = fs::File::open(&status_file_path).expect(&format!("Can't open '{}'", status_file_path));
-        let bufreader = BufReader::new(file);
-        let mut p = Process{name: "".to_owned(), pid: 0, ppid: 0};
-        bufreader.lines().filter_map(|x| x.ok()).for_each(|x| fill_process(&mut p, &x));
-        p
+    fn new(process_dir: &path::Path, pid: u32) -> Result<Self, ProcError> {
+        let vanished = |e| ProcError::from_io(e, pid);
+        let file = fs::File::open(process_dir.join("status")).map_err(vanished)?;
+        let mut p = Process { dir: process_dir.to_path_buf(), ..Process::default() };
+        for line in BufReader::new(file).lines() {
+            fill_process(&mut p, &line.map_err(vanished)?)?;
+        }
+
+        let stat = fs::read_to_string(process_dir.join("stat")).map_err(vanished)?;
+        fill_stat(&mut p, &stat)?;
+
+        p.cmdline = fs::read(process_dir.join("cmdline")).map(split_nul).unwrap_or_default();
+        p.exe = fs::read_link(process_dir.join("exe")).ok();
+        p.cwd = fs::read_link(process_dir.join("cwd")).ok();
+        p.environ = fs::read(process_dir.join("environ")).ok().map(to_environ);
+        Ok(p)
+    }
+}
+
+#[derive(Debug)]
+pub enum ProcError {
+    IO(io::Error),
+    /// The process exits while it's being read.
+    Vanished(u32),
+    IncorrectData(String),
+}
+
+impl ProcError {
+    /// A file of an exited process is absent or can't be read any more.
+    fn from_io(e: io::Error, pid: u32) -> Self {
+        match (e.kind(), e.raw_os_error()) {
+            (io::ErrorKind::NotFound, _) | (_, Some(libc::ESRCH)) => ProcError::Vanished(pid),
+            _ => ProcError::IO(e),
+        }
     }
+}
 
-    pub fn opened_files(&self) -> impl Iterator<Item=path::PathBuf> {
-        let process_file_desctriptors_path: path::PathBuf = [
-            "/proc", &self.pid.to_string(), "fd",
-        ].iter().collect();
-        let file_descriptors_dir_reading = process_file_desctriptors_path.as_path().read_dir().unwrap();
-        let file_descriptors_dir_entries = file_descriptors_dir_reading.filter_map(|x| x.ok());
-        let process_opened_files = file_descriptors_dir_entries.map(|x| x.path()).map(|x| fs::read_link(x));
-        process_opened_files.filter_map(|x| x.ok()).filter(|x| x.is_file())
+impl fmt::Display for ProcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcError::IO(e) => write!(f, "fail reading procfs: {}", e),
+            ProcError::Vanished(pid) => write!(f, "process {} has exited", pid),
+            ProcError::IncorrectData(x) => write!(f, "incorrect procfs data: {}", x),
+        }
     }
 }
 
-pub fn get_processes(uid: u32) -> impl Iterator<Item=Process> {
-    let proc_dir_reading = fs::read_dir("/proc/").unwrap();
-    let proc_dir_entries = proc_dir_reading.filter_map(|x| x.ok()).filter(
-        move |x| is_user_folder(x, uid) && is_process_folder(x)
-    );
-    proc_dir_entries.map(|x| Process::new(x.path().join("status").to_str().unwrap()))
+impl From<io::Error> for ProcError {
+    fn from(e: io::Error) -> Self {
+        ProcError::IO(e)
+    }
 }
 
-fn is_process_folder(e: &fs::DirEntry) -> bool {
-    e.metadata().ok().map_or(false, |x| x.is_dir()) &&
-    e.file_name().to_str().map_or(false, |x| x.parse::<u32>().is_ok())
+/// Mount point of procfs. Processes exiting during enumeration are skipped.
+#[derive(Clone, Debug)]
+pub struct Procfs {
+    root: path::PathBuf,
 }
 
-pub fn get_all_processes() -> impl Iterator<Item=Process> {
-    let proc_dir_entries = fs::read_dir("/proc/").unwrap();
-    let process_folders = proc_dir_entries.filter_map(|x| x.ok()).filter(is_process_folder);
-    process_folders.map(|x| Process::new(x.path().join("status").to_str().unwrap()))
+impl Default for Procfs {
+    fn default() -> Self {
+        Procfs::new("/proc")
+    }
 }
 
-pub fn print_processes_uses_file(pattern: &str) {
-    for process in get_all_processes() {
-        for f in process.opened_files().filter(|x| is_file_path_desited(x, pattern)).take(1) {
-            match f.to_str() {
-                Some(x) => println!("{} -> {}", process.name, x),
-                None => {
-                    panic!("Can't read link {} of process {}", f.as_path().display(), process.name)
-                },
-            };
+impl Procfs {
+    pub fn new<P: AsRef<path::Path>>(root: P) -> Self {
+        Procfs { root: root.as_ref().to_path_buf() }
+    }
+
+    pub fn process(&self, pid: u32) -> Result<Process, ProcError> {
+        Process::new(&self.root.join(pid.to_string()), pid)
+    }
+
+    pub fn processes(&self) -> Result<impl Iterator<Item=Result<Process, ProcError>>, ProcError> {
+        self.filter_processes(|_| true)
+    }
+
+    /// Processes which `/proc/<pid>` directory is owned by `uid`.
+    pub fn user_processes(&self, uid: u32) -> Result<impl Iterator<Item=Result<Process, ProcError>>, ProcError> {
+        self.filter_processes(move |x| is_user_folder(x, uid))
+    }
+
+    fn filter_processes<F>(&self, filter: F) -> Result<impl Iterator<Item=Result<Process, ProcError>>, ProcError>
+    where
+        F: Fn(&fs::DirEntry) -> bool,
+    {
+        let proc_dir_reading = fs::read_dir(&self.root)?;
+        let proc_dir_entries = proc_dir_reading.filter_map(|x| x.ok());
+        let processes = proc_dir_entries.filter_map(move |x| {
+            let pid = process_folder_pid(&x)?;
+            if !filter(&x) {
+                return None;
+            }
+            match Process::new(&x.path(), pid) {
+                Err(ProcError::Vanished(_)) => None,
+                x => Some(x),
+            }
+        });
+        Ok(processes)
+    }
+}
+
+pub fn get_processes(uid: u32) -> Result<impl Iterator<Item=Result<Process, ProcError>>, ProcError> {
+    Procfs::default().user_processes(uid)
+}
+
+fn process_folder_pid(e: &fs::DirEntry) -> Option<u32> {
+    if !e.metadata().is_ok_and(|x| x.is_dir()) {
+        return None;
+    }
+    e.file_name().to_str()?.parse().ok()
+}
+
+pub fn get_all_processes() -> Result<impl Iterator<Item=Result<Process, ProcError>>, ProcError> {
+    Procfs::default().processes()
+}
+
+/// Prints every descriptor which target contains `pattern`.
+pub fn print_processes_uses_file(pattern: &str) -> Result<(), ProcError> {
+    for process in get_all_processes()? {
+        let process = process?;
+        // descriptors of processes of other users can't be read.
+        let opened_files = match process.opened_files() {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        for f in opened_files.iter().filter(|x| is_file_path_desited(&x.target, pattern)) {
+            println!("{} {} {} -> {}", process.name, process.pid, f.fd, f.target.display());
         }
     }
+    Ok(())
 }
 
-fn is_file_path_desited(filepath: &path::PathBuf, pattern: &str) -> bool {
-    match filepath.to_str() {
-        None => false,
-        Some(x) => x.contains(pattern),
+fn is_file_path_desited(filepath: &path::Path, pattern: &str) -> bool {
+    filepath.to_string_lossy().contains(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status() {
+        let status = "Name:\tcat\nState:\tR (running)\nPid:\t42\nPPid:\t7\n\
+                      Uid:\t1000\t1000\t1000\t1000\nGid:\t100\t101\t102\t103\n\
+                      VmSize:\t    8112 kB\nVmRSS:\t     980 kB\nThreads:\t3\n\
+                      voluntary_ctxt_switches:\t150\nnonvoluntary_ctxt_switches:\t7\n";
+        let mut p = Process::default();
+        status.lines().for_each(|x| fill_process(&mut p, x).unwrap());
+        assert_eq!(p.name, "cat");
+        assert_eq!(p.pid, 42);
+        assert_eq!(p.ppid, 7);
+        assert_eq!(p.uid, Ids { real: 1000, effective: 1000, saved: 1000, filesystem: 1000 });
+        assert_eq!(p.gid, Ids { real: 100, effective: 101, saved: 102, filesystem: 103 });
+        assert_eq!(p.vm_size, Some(8112 * 1024));
+        assert_eq!(p.vm_rss, Some(980 * 1024));
+        assert_eq!(p.threads, 3);
+        assert_eq!((p.voluntary_switches, p.involuntary_switches), (150, 7));
+    }
+
+    #[test]
+    fn stat() {
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+        let stat = format!(
+            "42 (a (b) c) S 7 42 42 0 -1 4194560 100 0 0 0 {} {} 0 0 20 0 1 0 {} 8306688 245",
+            ticks * 3, ticks / 2, ticks * 10,
+        );
+        let mut p = Process::default();
+        fill_stat(&mut p, &stat).unwrap();
+        assert_eq!(p.state, State::Sleeping);
+        assert_eq!(p.utime, Duration::from_secs(3));
+        assert_eq!(p.stime, Duration::from_millis(500));
+        assert_eq!(p.start_time, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn cmdline_and_environ() {
+        assert_eq!(split_nul(b"ls\0-l\0a b\0".to_vec()), vec!["ls", "-l", "a b"]);
+        assert!(split_nul(vec![]).is_empty());
+        let environ = to_environ(b"HOME=/root\0A=b=c\0EMPTY=\0".to_vec());
+        let expected: Vec<(OsString, OsString)> = vec![
+            ("HOME".into(), "/root".into()), ("A".into(), "b=c".into()), ("EMPTY".into(), "".into()),
+        ];
+        assert_eq!(environ, expected);
+    }
+
+    #[test]
+    fn current_process() {
+        let p = Procfs::default().process(std::process::id()).unwrap();
+        assert_eq!(p.pid, std::process::id());
+        assert_ne!(p.state, State::Zombie);
+        assert!(p.threads >= 1);
+        assert!(p.vm_rss.is_some());
+        assert_eq!(p.exe, std::env::current_exe().ok());
+        assert!(!p.cmdline.is_empty());
+        assert!(p.environ.is_some());
+    }
+
+    /// Fake procfs removed on drop.
+    pub(super) struct FakeProc(pub(super) path::PathBuf);
+
+    impl FakeProc {
+        pub(super) fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("procfs_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            FakeProc(root)
+        }
+
+        pub(super) fn add(&self, pid: &str, status: &str, stat: &str) {
+            let dir = self.0.join(pid);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("status"), status).unwrap();
+            fs::write(dir.join("stat"), stat).unwrap();
+            fs::write(dir.join("cmdline"), b"init\0--fake\0").unwrap();
+        }
+
+        /// Writes a file at `relative` path creating missing directories.
+        pub(super) fn write(&self, relative: &str, content: &str) {
+            let file = self.0.join(relative);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        }
+
+        /// Adds a descriptor `fd` of `pid` pointing to `target`.
+        pub(super) fn fd(&self, pid: &str, fd: u32, target: &str) {
+            let dir = self.0.join(pid);
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            std::os::unix::fs::symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+            self.write(&format!("{}/fdinfo/{}", pid, fd), "pos:\t0\nflags:\t02\n");
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    pub(super) const STAT: &str = "1 (init) S 0 1 1 0 -1 4194560 100 0 0 0 5 7 0 0 20 0 1 0 9 8306688 245";
+
+    #[test]
+    fn fake_procfs() {
+        let procfs = FakeProc::new("fake");
+        procfs.add("1", "Name:\tinit\nPid:\t1\nPPid:\t0\n", STAT);
+        procfs.add("2", "Name:\tbroken\nPid:\tabc\n", STAT);
+        procfs.add("3", "Name:\tbad_stat\nPid:\t3\n", "3 bad_stat");
+        // the process exits between listing of the directory and reading its files.
+        fs::create_dir(procfs.0.join("4")).unwrap();
+        fs::create_dir(procfs.0.join("self")).unwrap();
+        fs::write(procfs.0.join("5"), "").unwrap();
+
+        let procfs_root = Procfs::new(&procfs.0);
+        let mut processes: Vec<_> = procfs_root.processes().unwrap().collect();
+        processes.sort_by_key(|x| match x {
+            Ok(p) => p.name.clone(),
+            Err(e) => e.to_string(),
+        });
+        assert_eq!(processes.len(), 3);
+        assert!(matches!(&processes[0], Err(ProcError::IncorrectData(x)) if x.contains("abc")));
+        assert!(matches!(&processes[1], Err(ProcError::IncorrectData(x)) if x.contains("bad_stat")));
+        let init = processes[2].as_ref().unwrap();
+        assert_eq!((init.pid, init.ppid, init.name.as_str()), (1, 0, "init"));
+        assert_eq!(init.cmdline, vec!["init", "--fake"]);
+        assert_eq!(init.exe, None);
+        assert!(init.opened_files().is_err());
+
+        assert!(matches!(procfs_root.process(4), Err(ProcError::Vanished(4))));
+        assert!(matches!(procfs_root.process(6), Err(ProcError::Vanished(6))));
+        let uid = fs::metadata(&procfs.0).unwrap().uid();
+        assert_eq!(procfs_root.user_processes(uid).unwrap().count(), 3);
+        assert_eq!(procfs_root.user_processes(uid + 1).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn absent_procfs() {
+        assert!(matches!(Procfs::new("/nonexistent/proc").processes(), Err(ProcError::IO(_))));
     }
 }
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::path;
+
+use super::FileKind;
+use super::ProcError;
+use super::Procfs;
+
+/// Table of `/proc/<pid>/net` a socket is listed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+impl Protocol {
+    const ALL: [Protocol; 5] = [Protocol::Tcp, Protocol::Tcp6, Protocol::Udp, Protocol::Udp6, Protocol::Unix];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+            Protocol::Unix => "unix",
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.file_name())
+    }
+}
+
+/// State of a socket. Inet sockets use the states of TCP, UDP ones are either `Established` or
+/// `Close`. Unix sockets use the rest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketState {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    NewSynRecv,
+    Unconnected,
+    Connecting,
+    Connected,
+    Disconnecting,
+    Unknown(u8),
+}
+
+impl SocketState {
+    fn inet(code: u8) -> Self {
+        match code {
+            1 => SocketState::Established,
+            2 => SocketState::SynSent,
+            3 => SocketState::SynRecv,
+            4 => SocketState::FinWait1,
+            5 => SocketState::FinWait2,
+            6 => SocketState::TimeWait,
+            7 => SocketState::Close,
+            8 => SocketState::CloseWait,
+            9 => SocketState::LastAck,
+            10 => SocketState::Listen,
+            11 => SocketState::Closing,
+            12 => SocketState::NewSynRecv,
+            x => SocketState::Unknown(x),
+        }
+    }
+
+    fn unix(code: u8, flags: u32) -> Self {
+        // __SO_ACCEPTCON is set for listening sockets.
+        if flags & 0x10000 != 0 {
+            return SocketState::Listen;
+        }
+        match code {
+            1 => SocketState::Unconnected,
+            2 => SocketState::Connecting,
+            3 => SocketState::Connected,
+            4 => SocketState::Disconnecting,
+            x => SocketState::Unknown(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Inet(SocketAddr),
+    /// Path of a unix socket. Abstract names start with `@`, unnamed sockets have no path.
+    Unix(Option<path::PathBuf>),
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Inet(x) => write!(f, "{}", x),
+            Address::Unix(Some(x)) => write!(f, "{}", x.display()),
+            Address::Unix(None) => f.write_str("*"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Socket {
+    pub protocol: Protocol,
+    pub local: Address,
+    /// Peer of an inet socket. Unix tables don't have it.
+    pub remote: Option<Address>,
+    pub state: SocketState,
+    /// Owner of an inet socket.
+    pub uid: Option<u32>,
+    pub inode: u64,
+}
+
+/// Socket held by a process.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub socket: Socket,
+    pub pid: u32,
+    pub name: String,
+    pub fd: u32,
+}
+
+fn incorrect(line: &str) -> ProcError {
+    ProcError::IncorrectData(format!("Can't parse socket `{}`", line.trim()))
+}
+
+fn hex(val: &str, line: &str) -> Result<u32, ProcError> {
+    u32::from_str_radix(val, 16).map_err(|_| incorrect(line))
+}
+
+/// Parses an address like `0100007F:0050`. The kernel prints every 32-bit word of an address as
+/// a number in the host byte order.
+fn inet_address(val: &str, line: &str) -> Result<SocketAddr, ProcError> {
+    let (ip, port) = val.split_once(':').ok_or_else(|| incorrect(line))?;
+    let port = u16::from_str_radix(port, 16).map_err(|_| incorrect(line))?;
+    let mut words = vec![];
+    for i in (0 .. ip.len()).step_by(8) {
+        let word = hex(ip.get(i .. i + 8).ok_or_else(|| incorrect(line))?, line)?;
+        words.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    match words.len() {
+        4 => Ok(SocketAddr::new(Ipv4Addr::new(words[0], words[1], words[2], words[3]).into(), port)),
+        16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&words);
+            Ok(SocketAddr::new(Ipv6Addr::from(octets).into(), port))
+        },
+        _ => Err(incorrect(line)),
+    }
+}
+
+/// Parses a line of `tcp`, `tcp6`, `udp` or `udp6` table.
+fn inet_socket(protocol: Protocol, line: &str) -> Result<Socket, ProcError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return Err(incorrect(line));
+    }
+
+    Ok(Socket {
+        protocol,
+        local: Address::Inet(inet_address(fields[1], line)?),
+        remote: Some(Address::Inet(inet_address(fields[2], line)?)),
+        state: SocketState::inet(hex(fields[3], line)? as u8),
+        uid: Some(fields[7].parse().map_err(|_| incorrect(line))?),
+        inode: fields[9].parse().map_err(|_| incorrect(line))?,
+    })
+}
+
+/// Parses a line of `unix` table. Columns are padded, a path after them may contain spaces.
+fn unix_socket(line: &str) -> Result<Socket, ProcError> {
+    let mut rest = line.trim();
+    let mut fields = vec![];
+    while fields.len() < 7 && !rest.is_empty() {
+        let end = rest.find(' ').unwrap_or(rest.len());
+        fields.push(&rest[.. end]);
+        rest = rest[end ..].trim_start();
+    }
+    if fields.len() < 7 {
+        return Err(incorrect(line));
+    }
+
+    let flags = hex(fields[3], line)?;
+    let state = hex(fields[5], line)? as u8;
+    let inode = fields[6].parse().map_err(|_| incorrect(line))?;
+    let path = Some(rest).filter(|x| !x.is_empty()).map(path::PathBuf::from);
+
+    Ok(Socket {
+        protocol: Protocol::Unix,
+        local: Address::Unix(path),
+        remote: None,
+        state: SocketState::unix(state, flags),
+        uid: None,
+        inode,
+    })
+}
+
+fn parse_table(protocol: Protocol, content: &str) -> Result<Vec<Socket>, ProcError> {
+    // the first line is a header.
+    content.lines().skip(1).filter(|x| !x.trim().is_empty()).map(|x| match protocol {
+        Protocol::Unix => unix_socket(x),
+        _ => inet_socket(protocol, x),
+    }).collect()
+}
+
+/// Sockets of a network namespace by inode.
+#[derive(Clone, Debug, Default)]
+pub struct SocketTable(HashMap<u64, Socket>);
+
+impl SocketTable {
+    pub fn get(&self, inode: u64) -> Option<&Socket> {
+        self.0.get(&inode)
+    }
+
+    pub fn sockets(&self) -> impl Iterator<Item=&Socket> {
+        self.0.values()
+    }
+}
+
+impl Procfs {
+    /// Reads sockets of the network namespace of `pid` from `/proc/<pid>/net`. Tables absent
+    /// because of a disabled protocol are skipped. A live process without `net`, like one in a
+    /// restricted namespace, is an I/O error.
+    pub fn sockets(&self, pid: u32) -> Result<SocketTable, ProcError> {
+        let process_dir = self.root.join(pid.to_string());
+        let net_dir = process_dir.join("net");
+        if !net_dir.exists() {
+            return Err(match process_dir.exists() {
+                true => ProcError::IO(io::Error::new(io::ErrorKind::NotFound, format!("{} is absent", net_dir.display()))),
+                false => ProcError::Vanished(pid),
+            });
+        }
+
+        let mut table = SocketTable::default();
+        for protocol in Protocol::ALL.iter() {
+            let content = match fs::read_to_string(net_dir.join(protocol.file_name())) {
+                Ok(x) => x,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(ProcError::from_io(e, pid)),
+            };
+            for socket in parse_table(*protocol, &content)? {
+                table.0.insert(socket.inode, socket);
+            }
+        }
+        Ok(table)
+    }
+
+    /// Lists sockets held by processes which descriptors can be read like `netstat -p`.
+    pub fn connections(&self) -> Result<Vec<Connection>, ProcError> {
+        // processes of a namespace share the tables so they are read once.
+        let mut tables: HashMap<path::PathBuf, SocketTable> = HashMap::new();
+        let mut connections = vec![];
+        for process in self.processes()? {
+            let process = process?;
+            let files = match process.opened_files() {
+                Ok(x) => x,
+                Err(ProcError::IO(_)) | Err(ProcError::Vanished(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let mut sockets = files.into_iter().filter_map(|x| match x.kind {
+                FileKind::Socket(inode) => Some((x.fd, inode)),
+                _ => None,
+            }).peekable();
+            if sockets.peek().is_none() {
+                continue;
+            }
+
+            let namespace = fs::read_link(process.dir.join("ns/net")).unwrap_or_else(|_| process.dir.clone());
+            let table = match tables.get(&namespace) {
+                Some(x) => x,
+                None => match self.sockets(process.pid) {
+                    Ok(x) => tables.entry(namespace).or_insert(x),
+                    Err(ProcError::IO(_)) | Err(ProcError::Vanished(_)) => continue,
+                    Err(e) => return Err(e),
+                },
+            };
+
+            for (fd, inode) in sockets {
+                if let Some(socket) = table.get(inode) {
+                    connections.push(Connection {
+                        socket: socket.clone(),
+                        pid: process.pid,
+                        name: process.name.clone(),
+                        fd,
+                    });
+                }
+            }
+        }
+        Ok(connections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::FakeProc;
+    use super::super::tests::STAT;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 17391 1 0000000000000000 100 0 0 10 0
+   1: 0F02000A:C350 5DB8D822:01BB 01 00000000:00000000 02:000A3D2C 00000000  1000        0 23411 2 0000000000000000 20 4 30 10 -1
+";
+
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000   101        0 30210 1 0000000000000000 100 0 0 10 0
+";
+
+    const UDP: &str = "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  512: 3500007F:0035 00000000:0000 07 00000000:00000000 00:00000000 00000000   102        0 18220 2 0000000000000000 0
+";
+
+    const UNIX: &str = "Num       RefCount Protocol Flags    Type St Inode Path
+0000000000000000: 00000002 00000000 00010000 0001 01 15110 /run/my app.sock
+0000000000000000: 00000003 00000000 00000000 0001 03 15120 @/tmp/.X11-unix/X0
+0000000000000000: 00000003 00000000 00000000 0001 03   966
+";
+
+    #[test]
+    fn addresses() {
+        let line = "";
+        assert_eq!(inet_address("0100007F:0277", line).unwrap(), "127.0.0.1:631".parse().unwrap());
+        assert_eq!(
+            inet_address("00000000000000000000000001000000:1F90", line).unwrap(),
+            "[::1]:8080".parse().unwrap(),
+        );
+        assert_eq!(
+            inet_address("B80D0120000000000000000001000000:0016", line).unwrap(),
+            "[2001:db8::1]:22".parse().unwrap(),
+        );
+        assert!(inet_address("0100007F", line).is_err());
+        assert!(inet_address("0100007:0277", line).is_err());
+    }
+
+    #[test]
+    fn tables() {
+        let tcp = parse_table(Protocol::Tcp, TCP).unwrap();
+        assert_eq!(tcp[0].state, SocketState::Listen);
+        assert_eq!(tcp[1], Socket {
+            protocol: Protocol::Tcp,
+            local: Address::Inet("10.0.2.15:50000".parse().unwrap()),
+            remote: Some(Address::Inet("34.216.184.93:443".parse().unwrap())),
+            state: SocketState::Established,
+            uid: Some(1000),
+            inode: 23411,
+        });
+
+        let unix = parse_table(Protocol::Unix, UNIX).unwrap();
+        let paths: Vec<String> = unix.iter().map(|x| x.local.to_string()).collect();
+        assert_eq!(paths, vec!["/run/my app.sock", "@/tmp/.X11-unix/X0", "*"]);
+        assert_eq!(unix[2].inode, 966);
+        let states: Vec<SocketState> = unix.iter().map(|x| x.state).collect();
+        assert_eq!(states, vec![SocketState::Listen, SocketState::Connected, SocketState::Connected]);
+        assert!(parse_table(Protocol::Udp, "header\n  0: 0100007F:0035 00000000:0000 07\n").is_err());
+    }
+
+    #[test]
+    fn fake_connections() {
+        let procfs = FakeProc::new("net");
+        procfs.add("7", "Name:\tserver\nPid:\t7\nPPid:\t1\n", STAT);
+        procfs.write("7/net/tcp", TCP);
+        procfs.write("7/net/tcp6", TCP6);
+        procfs.write("7/net/udp", UDP);
+        procfs.write("7/net/unix", UNIX);
+        procfs.fd("7", 3, "socket:[17391]");
+        procfs.fd("7", 4, "socket:[15110]");
+        procfs.fd("7", 5, "pipe:[99]");
+        // the socket is created after the tables are read.
+        procfs.fd("7", 6, "socket:[77777]");
+        procfs.add("8", "Name:\tclient\nPid:\t8\nPPid:\t1\n", STAT);
+        procfs.fd("8", 0, "socket:[30210]");
+        procfs.write("8/net/tcp6", TCP6);
+        procfs.add("9", "Name:\tidle\nPid:\t9\nPPid:\t1\n", STAT);
+        // a process of a namespace which tables can't be read.
+        procfs.add("10", "Name:\tjailed\nPid:\t10\nPPid:\t1\n", STAT);
+        procfs.fd("10", 3, "socket:[17391]");
+
+        let procfs_root = Procfs::new(&procfs.0);
+        let table = procfs_root.sockets(7).unwrap();
+        assert_eq!(table.sockets().count(), 7);
+        assert_eq!(table.get(18220).unwrap().local, Address::Inet("127.0.0.53:53".parse().unwrap()));
+        // the process is alive but has no tables.
+        assert!(matches!(procfs_root.sockets(9), Err(ProcError::IO(_))));
+        assert!(matches!(procfs_root.sockets(12), Err(ProcError::Vanished(12))));
+
+        let mut connections = procfs_root.connections().unwrap();
+        connections.sort_by_key(|x| (x.pid, x.fd));
+        let found: Vec<(u32, u32, Protocol, u64)> = connections.iter()
+            .map(|x| (x.pid, x.fd, x.socket.protocol, x.socket.inode))
+            .collect();
+        assert_eq!(found, vec![
+            (7, 3, Protocol::Tcp, 17391), (7, 4, Protocol::Unix, 15110), (8, 0, Protocol::Tcp6, 30210),
+        ]);
+        assert_eq!(connections[2].name, "client");
+        assert_eq!(connections[2].socket.local.to_string(), "[::1]:8080");
+    }
+}
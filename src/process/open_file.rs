@@ -0,0 +1,317 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+use std::path;
+
+use super::ProcError;
+use super::Process;
+use super::Procfs;
+
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+
+/// What a file descriptor refers to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Regular,
+    Directory,
+    CharDevice,
+    BlockDevice,
+    /// Named pipe.
+    Fifo,
+    /// Socket, the number is its inode.
+    Socket(u64),
+    /// Anonymous pipe, the number is its inode.
+    Pipe(u64),
+    /// Anonymous inode like `eventfd` or `inotify`.
+    AnonInode(String),
+    Unknown,
+}
+
+/// File opened by a process.
+#[derive(Clone, Debug)]
+pub struct OpenFile {
+    pub fd: u32,
+    /// Target of `/proc/<pid>/fd/<fd>` without the ` (deleted)` suffix.
+    pub target: path::PathBuf,
+    pub kind: FileKind,
+    /// The file is removed while it's open.
+    pub deleted: bool,
+    /// `O_*` flags the file is opened with.
+    pub flags: i32,
+    /// Offset of the descriptor.
+    pub pos: u64,
+    /// Device and inode of the file. `None` if the file can't be stat'ed.
+    pub dev: Option<u64>,
+    pub ino: Option<u64>,
+}
+
+/// Parses a link target like `socket:[1234]` or `anon_inode:[eventfd]`.
+fn pseudo_file(target: &[u8]) -> Option<FileKind> {
+    let colon = target.iter().position(|x| *x == b':')?;
+    let (name, value) = (&target[.. colon], &target[colon + 1 ..]);
+    let value = String::from_utf8_lossy(value);
+    let bracketed = value.strip_prefix('[').and_then(|x| x.strip_suffix(']'));
+    let inode = || bracketed.and_then(|x| x.parse().ok());
+    match name {
+        b"socket" => inode().map(FileKind::Socket),
+        b"pipe" => inode().map(FileKind::Pipe),
+        b"anon_inode" => Some(FileKind::AnonInode(bracketed.unwrap_or(&value).to_owned())),
+        _ => None,
+    }
+}
+
+fn kind_of(meta: &fs::Metadata) -> FileKind {
+    let file_type = meta.file_type();
+    if file_type.is_file() {
+        FileKind::Regular
+    } else if file_type.is_dir() {
+        FileKind::Directory
+    } else if file_type.is_char_device() {
+        FileKind::CharDevice
+    } else if file_type.is_block_device() {
+        FileKind::BlockDevice
+    } else if file_type.is_fifo() {
+        FileKind::Fifo
+    } else if file_type.is_socket() {
+        FileKind::Socket(meta.ino())
+    } else {
+        FileKind::Unknown
+    }
+}
+
+/// Fills `flags` and `pos` from the content of `/proc/<pid>/fdinfo/<fd>`.
+fn fill_fdinfo(f: &mut OpenFile, fdinfo: &str) -> Result<(), ProcError> {
+    let incorrect = |x: &str| ProcError::IncorrectData(format!("Can't parse fdinfo `{}`", x));
+    for line in fdinfo.lines() {
+        let mut line_parts = line.splitn(2, ':');
+        let (name, value) = match (line_parts.next(), line_parts.next()) {
+            (Some(name), Some(value)) => (name, value.trim()),
+            _ => continue,
+        };
+
+        match name {
+            "pos" => f.pos = value.parse().map_err(|_| incorrect(line))?,
+            "flags" => f.flags = i32::from_str_radix(value, 8).map_err(|_| incorrect(line))?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+impl OpenFile {
+    fn new(process_dir: &path::Path, fd: u32, pid: u32) -> Result<Self, ProcError> {
+        let vanished = |e| ProcError::from_io(e, pid);
+        let link = process_dir.join("fd").join(fd.to_string());
+        let target = fs::read_link(&link).map_err(vanished)?;
+        let bytes = target.as_os_str().as_bytes();
+        let deleted = bytes.starts_with(b"/") && bytes.ends_with(DELETED_SUFFIX);
+        let target = match deleted {
+            true => path::PathBuf::from(OsStr::from_bytes(&bytes[.. bytes.len() - DELETED_SUFFIX.len()])),
+            false => target,
+        };
+
+        // the link is followed even if the file is deleted or has no path at all.
+        let meta = fs::metadata(&link).ok();
+        let kind = match (pseudo_file(target.as_os_str().as_bytes()), &meta) {
+            (Some(kind), _) => kind,
+            (None, Some(meta)) => kind_of(meta),
+            (None, None) => FileKind::Unknown,
+        };
+
+        let mut f = OpenFile {
+            fd,
+            target,
+            kind,
+            deleted,
+            flags: 0,
+            pos: 0,
+            dev: meta.as_ref().map(|x| x.dev()),
+            ino: meta.as_ref().map(|x| x.ino()),
+        };
+        let fdinfo = fs::read_to_string(process_dir.join("fdinfo").join(fd.to_string())).map_err(vanished)?;
+        fill_fdinfo(&mut f, &fdinfo)?;
+        Ok(f)
+    }
+}
+
+impl Process {
+    /// Lists every descriptor of the process ordered by number. Descriptors closed during the
+    /// listing are skipped.
+    pub fn opened_files(&self) -> Result<Vec<OpenFile>, ProcError> {
+        let vanished = |e| ProcError::from_io(e, self.pid);
+        let mut fds = vec![];
+        for entry in self.dir.join("fd").read_dir().map_err(vanished)? {
+            let entry = entry.map_err(vanished)?;
+            if let Some(fd) = entry.file_name().to_str().and_then(|x| x.parse().ok()) {
+                fds.push(fd);
+            }
+        }
+        fds.sort_unstable();
+
+        let mut files = vec![];
+        for fd in fds {
+            match OpenFile::new(&self.dir, fd, self.pid) {
+                Ok(x) => files.push(x),
+                Err(ProcError::Vanished(_)) if self.dir.exists() => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Which open files to look for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Holding {
+    /// Target of a descriptor is the path.
+    Path(path::PathBuf),
+    /// Device and inode, so any hard link of the file is found.
+    Inode(u64, u64),
+    /// Any file on the device.
+    Mount(u64),
+}
+
+impl Holding {
+    /// Looks for the file at `path` by its inode.
+    pub fn file<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Holding::Inode(meta.dev(), meta.ino()))
+    }
+
+    /// Looks for files on the file system `path` belongs to like `fuser -m`.
+    pub fn mount<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        Ok(Holding::Mount(fs::metadata(path)?.dev()))
+    }
+
+    pub fn matches(&self, f: &OpenFile) -> bool {
+        match self {
+            Holding::Path(path) => f.target == *path,
+            Holding::Inode(dev, ino) => f.dev == Some(*dev) && f.ino == Some(*ino),
+            Holding::Mount(dev) => f.dev == Some(*dev),
+        }
+    }
+}
+
+impl Procfs {
+    /// Finds processes holding files described by `holding` together with the matching files.
+    /// Descriptors of processes of other users are skipped unless they can be read, so are
+    /// processes which can't be read at all.
+    pub fn holders(&self, holding: &Holding) -> Result<Vec<(Process, Vec<OpenFile>)>, ProcError> {
+        let mut holders = vec![];
+        for process in self.processes()? {
+            let process = match process {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            let files = match process.opened_files() {
+                Ok(x) => x,
+                Err(ProcError::IO(ref e)) if e.kind() == io::ErrorKind::PermissionDenied => continue,
+                Err(ProcError::Vanished(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let files: Vec<OpenFile> = files.into_iter().filter(|x| holding.matches(x)).collect();
+            if !files.is_empty() {
+                holders.push((process, files));
+            }
+        }
+        Ok(holders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::os::unix::fs::symlink;
+    use std::os::unix::io::AsRawFd;
+    use super::super::tests::FakeProc;
+
+    #[test]
+    fn pseudo_files() {
+        assert_eq!(pseudo_file(b"socket:[1234]"), Some(FileKind::Socket(1234)));
+        assert_eq!(pseudo_file(b"pipe:[42]"), Some(FileKind::Pipe(42)));
+        assert_eq!(pseudo_file(b"anon_inode:[eventfd]"), Some(FileKind::AnonInode("eventfd".to_owned())));
+        assert_eq!(pseudo_file(b"anon_inode:inotify"), Some(FileKind::AnonInode("inotify".to_owned())));
+        assert_eq!(pseudo_file(b"/tmp/a:b"), None);
+        assert_eq!(pseudo_file(b"socket:1234"), None);
+    }
+
+    #[test]
+    fn fdinfo() {
+        let mut f = OpenFile {
+            fd: 3, target: "/tmp/a".into(), kind: FileKind::Regular, deleted: false,
+            flags: 0, pos: 0, dev: None, ino: None,
+        };
+        fill_fdinfo(&mut f, "pos:\t120\nflags:\t02000002\nmnt_id:\t25\nino:\t7\n").unwrap();
+        assert_eq!(f.pos, 120);
+        assert_eq!(f.flags, libc::O_CLOEXEC | libc::O_RDWR);
+        assert!(fill_fdinfo(&mut f, "pos:\tx\n").is_err());
+    }
+
+    #[test]
+    fn fake_descriptors() {
+        let procfs = FakeProc::new("fd");
+        procfs.add("7", "Name:\tfake\nPid:\t7\nPPid:\t1\n", "7 (fake) S 1 7 7 0 -1 0 0 0 0 0 1 1 0 0 20 0 1 0 9 0 0");
+        let targets = ["socket:[11]", "pipe:[12]", "anon_inode:[eventfd]", "/gone/file (deleted)"];
+        for (fd, target) in targets.iter().enumerate() {
+            procfs.fd("7", fd as u32, target);
+            procfs.write(&format!("7/fdinfo/{}", fd), &format!("pos:\t{}\nflags:\t02\n", fd));
+        }
+        // closed between listing and reading.
+        symlink("pipe:[13]", procfs.0.join("7/fd/9")).unwrap();
+
+        let process = Procfs::new(&procfs.0).process(7).unwrap();
+        let files = process.opened_files().unwrap();
+
+        let kinds: Vec<FileKind> = files.iter().map(|x| x.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            FileKind::Socket(11), FileKind::Pipe(12), FileKind::AnonInode("eventfd".to_owned()), FileKind::Unknown,
+        ]);
+        assert_eq!(files[3].target, path::PathBuf::from("/gone/file"));
+        assert!(files[3].deleted);
+        assert!(files.iter().all(|x| x.flags == libc::O_RDWR && x.pos == x.fd as u64));
+
+        // a process which can't be read doesn't stop the search.
+        procfs.add("8", "Name:\tbroken\n", "8 (broken)");
+        assert!(matches!(Procfs::new(&procfs.0).process(8), Err(ProcError::IncorrectData(_))));
+        let holders = Procfs::new(&procfs.0).holders(&Holding::Path(path::PathBuf::from("/gone/file"))).unwrap();
+        assert_eq!(holders.iter().map(|x| x.0.pid).collect::<Vec<u32>>(), vec![7]);
+    }
+
+    #[test]
+    fn own_descriptors() {
+        let path = std::env::temp_dir().join(format!("open_file_{}", std::process::id()));
+        fs::write(&path, b"0123456789").unwrap();
+        let mut file = fs::File::open(&path).unwrap();
+        let mut buf = [0; 4];
+        file.read_exact(&mut buf).unwrap();
+
+        let procfs = Procfs::default();
+        let process = procfs.process(std::process::id()).unwrap();
+        let fd = file.as_raw_fd() as u32;
+        let open_file = process.opened_files().unwrap().into_iter().find(|x| x.fd == fd).unwrap();
+        assert_eq!(open_file.target, path);
+        assert_eq!(open_file.kind, FileKind::Regular);
+        assert_eq!(open_file.pos, 4);
+        assert_eq!(open_file.flags & libc::O_ACCMODE, libc::O_RDONLY);
+        assert!(!open_file.deleted);
+
+        let holding = Holding::file(&path).unwrap();
+        let holders = procfs.holders(&holding).unwrap();
+        let own = holders.iter().find(|(p, _)| p.pid == std::process::id()).unwrap();
+        assert!(own.1.iter().any(|x| x.fd == fd));
+        assert!(holding.matches(&open_file));
+        assert!(Holding::mount(&path).unwrap().matches(&open_file));
+        assert!(Holding::Path(path.clone()).matches(&open_file));
+
+        fs::remove_file(&path).unwrap();
+        let open_file = process.opened_files().unwrap().into_iter().find(|x| x.fd == fd).unwrap();
+        assert!(open_file.deleted);
+        assert_eq!(open_file.target, path);
+        assert!(holding.matches(&open_file));
+    }
+}
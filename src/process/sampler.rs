@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use crate::container::Heap;
+
+use super::ticks_per_second;
+use super::to_int;
+use super::ProcError;
+use super::Procfs;
+
+/// Usage of a process between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub pid: u32,
+    pub name: String,
+    /// Percentage of one CPU like top(1), so it exceeds 100 for processes running on several
+    /// CPUs.
+    pub cpu: f64,
+    /// Resident set size as a percentage of the whole memory.
+    pub memory: f64,
+    /// Bytes per second read from and written to storage. `None` if `/proc/<pid>/io` belongs to
+    /// another user.
+    pub read_rate: Option<f64>,
+    pub write_rate: Option<f64>,
+    /// Context switches per second, both voluntary and involuntary.
+    pub switch_rate: f64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counters {
+    start_time: Duration,
+    cpu: Duration,
+    io: Option<(u64, u64)>,
+    switches: u64,
+}
+
+struct Snapshot {
+    /// Time spent by all CPUs.
+    cpu_ticks: u64,
+    processes: HashMap<u32, Counters>,
+}
+
+/// Takes snapshots of processes and computes their usage between the snapshots like top(1).
+pub struct ProcessSampler {
+    procfs: Procfs,
+    cpus: u64,
+    memory: u64,
+    previous: Option<Snapshot>,
+}
+
+/// Sums the first line of `/proc/stat` and counts CPUs. Guest time is already a part of user
+/// time so it's skipped.
+fn parse_stat(stat: &str) -> Result<(u64, u64), ProcError> {
+    let incorrect = || ProcError::IncorrectData("Can't parse /proc/stat".to_owned());
+    let mut lines = stat.lines();
+    let total = lines.next().and_then(|x| x.strip_prefix("cpu ")).ok_or_else(incorrect)?;
+    let mut ticks = 0;
+    for x in total.split_whitespace().take(8) {
+        ticks += to_int::<u64>(x)?;
+    }
+
+    let is_cpu = |x: &&str| x.strip_prefix("cpu").is_some_and(|x| x.starts_with(|c: char| c.is_ascii_digit()));
+    let cpus = lines.filter(is_cpu).count() as u64;
+    Ok((ticks, cpus.max(1)))
+}
+
+fn parse_meminfo(meminfo: &str) -> Result<u64, ProcError> {
+    let total = meminfo.lines().find_map(|x| x.strip_prefix("MemTotal:"));
+    let total = total.ok_or_else(|| ProcError::IncorrectData("Can't find MemTotal".to_owned()))?;
+    super::to_bytes(total)
+}
+
+/// Reads bytes read from and written to storage from `/proc/<pid>/io`.
+fn parse_io(io: &str) -> Result<(u64, u64), ProcError> {
+    let (mut read, mut written) = (None, None);
+    for line in io.lines() {
+        match line.split_once(':') {
+            Some(("read_bytes", x)) => read = Some(to_int(x)?),
+            Some(("write_bytes", x)) => written = Some(to_int(x)?),
+            _ => (),
+        }
+    }
+    match (read, written) {
+        (Some(read), Some(written)) => Ok((read, written)),
+        _ => Err(ProcError::IncorrectData(format!("Can't parse io `{}`", io.trim()))),
+    }
+}
+
+fn rate(current: u64, previous: u64, seconds: f64) -> f64 {
+    current.saturating_sub(previous) as f64 / seconds
+}
+
+impl ProcessSampler {
+    pub fn new(procfs: Procfs) -> Result<Self, ProcError> {
+        let (_, cpus) = parse_stat(&fs::read_to_string(procfs.root.join("stat"))?)?;
+        let memory = parse_meminfo(&fs::read_to_string(procfs.root.join("meminfo"))?)?;
+        Ok(ProcessSampler { procfs, cpus, memory, previous: None })
+    }
+
+    /// Takes a snapshot and computes usage since the previous one. The first call only takes the
+    /// snapshot and returns nothing. Processes started in between are counted from their start.
+    pub fn sample(&mut self) -> Result<Vec<Sample>, ProcError> {
+        let (cpu_ticks, _) = parse_stat(&fs::read_to_string(self.procfs.root.join("stat"))?)?;
+        let mut snapshot = Snapshot { cpu_ticks, processes: HashMap::new() };
+        let mut current = vec![];
+        for process in self.procfs.processes()? {
+            let process = process?;
+            let io = match fs::read_to_string(process.dir.join("io")) {
+                Ok(x) => Some(parse_io(&x)?),
+                Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => None,
+                // kernels without I/O accounting have no `io`.
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound && process.dir.exists() => None,
+                Err(e) => match ProcError::from_io(e, process.pid) {
+                    ProcError::Vanished(_) => continue,
+                    e => return Err(e),
+                },
+            };
+            let counters = Counters {
+                start_time: process.start_time,
+                cpu: process.utime + process.stime,
+                io,
+                switches: process.voluntary_switches + process.involuntary_switches,
+            };
+            snapshot.processes.insert(process.pid, counters);
+            current.push((process, counters));
+        }
+
+        let previous = match self.previous.replace(snapshot) {
+            Some(x) => x,
+            None => return Ok(vec![]),
+        };
+        // wall time passed is the CPU time of all CPUs divided between them.
+        let ticks = cpu_ticks.saturating_sub(previous.cpu_ticks).max(1);
+        let seconds = ticks as f64 / self.cpus as f64 / ticks_per_second() as f64;
+
+        Ok(current.into_iter().map(|(process, counters)| {
+            let before = previous.processes.get(&process.pid)
+                // the pid is reused by another process.
+                .filter(|x| x.start_time == counters.start_time)
+                .copied()
+                .unwrap_or_default();
+            let io = counters.io.map(|(read, written)| {
+                let (read_before, written_before) = before.io.unwrap_or_default();
+                (rate(read, read_before, seconds), rate(written, written_before, seconds))
+            });
+
+            Sample {
+                pid: process.pid,
+                name: process.name,
+                cpu: counters.cpu.saturating_sub(before.cpu).as_secs_f64() / seconds * 100.0,
+                memory: process.vm_rss.unwrap_or(0) as f64 / self.memory as f64 * 100.0,
+                read_rate: io.map(|x| x.0),
+                write_rate: io.map(|x| x.1),
+                switch_rate: rate(counters.switches, before.switches, seconds),
+            }
+        }).collect())
+    }
+
+    /// Picks `n` samples with the greatest `key` ordered from the greatest.
+    pub fn top<F: Fn(&Sample) -> f64>(samples: Vec<Sample>, n: usize, key: F) -> Vec<Sample> {
+        // the least of the best samples found so far is on the top of the heap.
+        let mut heap = Heap::min();
+        let mut len = 0;
+        for (i, sample) in samples.iter().enumerate() {
+            heap.push((key(sample), i));
+            len += 1;
+            if len > n {
+                heap.pop();
+                len -= 1;
+            }
+        }
+
+        let mut indices = vec![];
+        while let Some((_, i)) = heap.pop() {
+            indices.push(i);
+        }
+        let mut samples: Vec<Option<Sample>> = samples.into_iter().map(Some).collect();
+        indices.iter().rev().filter_map(|x| samples[*x].take()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::FakeProc;
+
+    fn status(name: &str, pid: u32, rss_kb: u64, switches: u64) -> String {
+        format!(
+            "Name:\t{}\nPid:\t{}\nPPid:\t1\nVmRSS:\t{} kB\nvoluntary_ctxt_switches:\t{}\nnonvoluntary_ctxt_switches:\t0\n",
+            name, pid, rss_kb, switches,
+        )
+    }
+
+    fn stat(pid: u32, utime: u64, stime: u64, start_time: u64) -> String {
+        format!("{} (x) S 1 1 1 0 -1 0 0 0 0 0 {} {} 0 0 20 0 1 0 {} 0 0", pid, utime, stime, start_time)
+    }
+
+    fn io(read: u64, written: u64) -> String {
+        format!("rchar: 1\nwchar: 1\nread_bytes: {}\nwrite_bytes: {}\ncancelled_write_bytes: 0\n", read, written)
+    }
+
+    #[test]
+    fn parse() {
+        let stat = "cpu  10 1 5 100 4 0 0 0 3 0\ncpu0 5 0 3 50 2 0 0 0 3 0\ncpu1 5 1 2 50 2 0 0 0 0 0\nintr 1\n";
+        assert_eq!(parse_stat(stat).unwrap(), (120, 2));
+        assert!(parse_stat("intr 1\n").is_err());
+        assert_eq!(parse_meminfo("MemTotal:       1000 kB\nMemFree: 1 kB\n").unwrap(), 1024000);
+        assert_eq!(parse_io(&io(10, 20)).unwrap(), (10, 20));
+        assert!(parse_io("rchar: 1\n").is_err());
+    }
+
+    #[test]
+    fn fake_procfs() {
+        let tick = ticks_per_second();
+        let procfs = FakeProc::new("sampler");
+        // two CPUs and 1000 kB of memory.
+        let cpu_stat = |ticks: u64| format!("cpu  {} 0 0 0 0 0 0 0 0 0\ncpu0 0\ncpu1 0\n", ticks);
+        procfs.write("stat", &cpu_stat(1000 * tick));
+        procfs.write("meminfo", "MemTotal:\t1000 kB\n");
+        procfs.add("10", &status("busy", 10, 250, 100), &stat(10, 5 * tick, tick, 7));
+        procfs.write("10/io", &io(4096, 0));
+        procfs.add("11", &status("idle", 11, 100, 5), &stat(11, tick, 0, 7));
+        procfs.add("12", &status("reused", 12, 10, 50), &stat(12, 3 * tick, 0, 7));
+
+        let mut sampler = ProcessSampler::new(Procfs::new(&procfs.0)).unwrap();
+        assert!(sampler.sample().unwrap().is_empty());
+
+        // 2 seconds pass.
+        procfs.write("stat", &cpu_stat(1004 * tick));
+        procfs.write("10/status", &status("busy", 10, 250, 300));
+        procfs.write("10/stat", &stat(10, 7 * tick, 2 * tick, 7));
+        procfs.write("10/io", &io(4096 + 2048, 8192));
+        procfs.write("12/stat", &stat(12, tick, 0, 900));
+        procfs.add("13", &status("new", 13, 0, 2), &stat(13, tick / 2, 0, 1003));
+        fs::remove_dir_all(procfs.0.join("11")).unwrap();
+
+        let mut samples = sampler.sample().unwrap();
+        samples.sort_by_key(|x| x.pid);
+        let pids: Vec<u32> = samples.iter().map(|x| x.pid).collect();
+        assert_eq!(pids, vec![10, 12, 13]);
+
+        let busy = &samples[0];
+        assert!((busy.cpu - 150.0).abs() < 1e-6, "{:?}", busy);
+        assert!((busy.memory - 25.0).abs() < 1e-6);
+        assert_eq!(busy.read_rate, Some(1024.0));
+        assert_eq!(busy.write_rate, Some(4096.0));
+        assert_eq!(busy.switch_rate, 100.0);
+        // the pid belongs to another process now, so all its time is spent in the interval.
+        assert!((samples[1].cpu - 50.0).abs() < 1e-6);
+        assert_eq!(samples[1].read_rate, None);
+        assert!((samples[2].cpu - 25.0).abs() < 1e-6);
+        assert_eq!(samples[2].switch_rate, 1.0);
+
+        let top = ProcessSampler::top(samples.clone(), 2, |x| x.cpu);
+        assert_eq!(top.iter().map(|x| x.pid).collect::<Vec<u32>>(), vec![10, 12]);
+        let top = ProcessSampler::top(samples.clone(), 5, |x| x.switch_rate);
+        assert_eq!(top.iter().map(|x| x.pid).collect::<Vec<u32>>(), vec![10, 12, 13]);
+        assert!(ProcessSampler::top(samples, 0, |x| x.cpu).is_empty());
+    }
+
+    #[test]
+    fn live() {
+        let mut sampler = ProcessSampler::new(Procfs::default()).unwrap();
+        sampler.sample().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let samples = sampler.sample().unwrap();
+        let own = samples.iter().find(|x| x.pid == std::process::id()).unwrap();
+        assert!(own.memory > 0.0);
+        assert!(own.read_rate.is_some());
+    }
+}
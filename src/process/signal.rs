@@ -0,0 +1,262 @@
+use std::fs;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::fill_stat;
+use super::ProcError;
+use super::Process;
+use super::ProcessTree;
+use super::State;
+
+/// Numbers of `pidfd_open` and `pidfd_send_signal` which the version of libc in use doesn't have.
+/// Only architectures using the common numbers are listed, alpha, ia64, mips and x32 differ.
+#[cfg(any(
+    all(target_arch = "x86_64", target_pointer_width = "64"),
+    target_arch = "x86",
+    target_arch = "arm",
+    target_arch = "aarch64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "riscv64",
+    target_arch = "s390x",
+))]
+const SYS_PIDFD: Option<(libc::c_long, libc::c_long)> = Some((434, 424));
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_pointer_width = "64"),
+    target_arch = "x86",
+    target_arch = "arm",
+    target_arch = "aarch64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "riscv64",
+    target_arch = "s390x",
+)))]
+const SYS_PIDFD: Option<(libc::c_long, libc::c_long)> = None;
+
+/// How often a process is checked if it can't be polled.
+const WAIT_STEP: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Hup,
+    Int,
+    Quit,
+    Kill,
+    Usr1,
+    Usr2,
+    Term,
+    Cont,
+    Stop,
+    Other(i32),
+}
+
+impl Signal {
+    fn raw(self) -> libc::c_int {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Int => libc::SIGINT,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Term => libc::SIGTERM,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Other(x) => x,
+        }
+    }
+}
+
+/// Opens a descriptor referring to the process with `pid`. `None` if the kernel or the
+/// architecture isn't supported.
+fn pidfd_open(pid: u32) -> io::Result<Option<OwnedFd>> {
+    let (sys_pidfd_open, _) = match SYS_PIDFD {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let fd = unsafe { libc::syscall(sys_pidfd_open, pid as libc::pid_t, 0) };
+    if fd >= 0 {
+        return Ok(Some(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) }));
+    }
+
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ENOSYS) => Ok(None),
+        _ => Err(e),
+    }
+}
+
+impl Process {
+    /// Reads the state of the process again. Fails if the pid belongs to another process now.
+    fn current_state(&self) -> Result<State, ProcError> {
+        let stat = fs::read_to_string(self.dir.join("stat")).map_err(|e| ProcError::from_io(e, self.pid))?;
+        let mut current = Process::default();
+        fill_stat(&mut current, &stat)?;
+        match current.start_time == self.start_time {
+            true => Ok(current.state),
+            false => Err(ProcError::Vanished(self.pid)),
+        }
+    }
+
+    /// Opens a pidfd and makes sure it refers to this process rather than a process which
+    /// reused the pid.
+    fn pidfd(&self) -> Result<Option<OwnedFd>, ProcError> {
+        let pidfd = pidfd_open(self.pid).map_err(|e| ProcError::from_io(e, self.pid))?;
+        // the pid can't be reused while the descriptor is open.
+        self.current_state()?;
+        Ok(pidfd)
+    }
+
+    /// Sends `signal` to the process. Fails with `Vanished` if the process has exited even if
+    /// its pid is taken by another process. Without pidfd support there is a short window when
+    /// a reused pid can be signaled.
+    pub fn signal(&self, signal: Signal) -> Result<(), ProcError> {
+        let res = match (self.pidfd()?, SYS_PIDFD) {
+            (Some(pidfd), Some((_, sys_pidfd_send_signal))) => unsafe {
+                libc::syscall(sys_pidfd_send_signal, pidfd.as_raw_fd(), signal.raw(), ptr::null::<libc::siginfo_t>(), 0)
+            },
+            _ => unsafe { libc::kill(self.pid as libc::pid_t, signal.raw()) as libc::c_long },
+        };
+
+        match res {
+            -1 => Err(ProcError::from_io(io::Error::last_os_error(), self.pid)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Waits until the process exits. A zombie is considered exited. Returns false if it's still
+    /// running after `timeout`.
+    pub fn wait_for_exit(&self, timeout: Duration) -> Result<bool, ProcError> {
+        let pidfd = match self.pidfd() {
+            Ok(x) => x,
+            Err(ProcError::Vanished(_)) => return Ok(true),
+            Err(e) => return Err(e),
+        };
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.current_state() {
+                Ok(State::Zombie) | Ok(State::Dead) | Err(ProcError::Vanished(_)) => return Ok(true),
+                Ok(_) => (),
+                Err(e) => return Err(e),
+            }
+
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return Ok(false);
+            }
+
+            match &pidfd {
+                // the descriptor becomes readable when the process exits.
+                Some(pidfd) => {
+                    let mut fds = libc::pollfd { fd: pidfd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+                    let millis = left.as_millis().clamp(1, libc::c_int::MAX as u128) as libc::c_int;
+                    let res = unsafe { libc::poll(&mut fds, 1, millis) };
+                    match res {
+                        -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                        -1 => return Err(ProcError::IO(io::Error::last_os_error())),
+                        0 => return Ok(false),
+                        _ => return Ok(true),
+                    }
+                },
+                None => thread::sleep(left.min(WAIT_STEP)),
+            }
+        }
+    }
+}
+
+impl ProcessTree {
+    /// Sends `signal` to the process with `pid` and its descendants, children before their
+    /// parents so they can't be reparented in between. Processes which have already exited are
+    /// skipped. Returns pids signaled.
+    pub fn kill_tree(&self, pid: u32, signal: Signal) -> Result<Vec<u32>, ProcError> {
+        let subtree = match self.subtree(pid) {
+            Some(x) => x,
+            None => return Err(ProcError::Vanished(pid)),
+        };
+
+        let mut signaled = vec![];
+        for node in subtree.post_order() {
+            match node.value.signal(signal) {
+                Ok(()) => signaled.push(node.value.pid),
+                Err(ProcError::Vanished(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(signaled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Procfs;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+
+    fn sleep() -> std::process::Child {
+        Command::new("sleep").arg("30").spawn().unwrap()
+    }
+
+    #[test]
+    fn signal_and_wait() {
+        let mut child = sleep();
+        let process = Procfs::default().process(child.id()).unwrap();
+        assert!(!process.wait_for_exit(Duration::from_millis(50)).unwrap());
+
+        process.signal(Signal::Term).unwrap();
+        assert!(process.wait_for_exit(Duration::from_secs(5)).unwrap());
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+        assert!(matches!(process.signal(Signal::Term), Err(ProcError::Vanished(_))));
+        assert!(process.wait_for_exit(Duration::from_secs(0)).unwrap());
+    }
+
+    #[test]
+    fn reused_pid() {
+        let mut child = sleep();
+        let mut process = Procfs::default().process(child.id()).unwrap();
+        // as if the process has exited and its pid is taken by another one.
+        process.start_time += Duration::from_secs(1);
+        assert!(matches!(process.signal(Signal::Kill), Err(ProcError::Vanished(_))));
+        assert!(matches!(child.try_wait(), Ok(None)));
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn kill_tree() {
+        let mut child = Command::new("sh").arg("-c").arg("sleep 30 & sleep 30 & wait").spawn().unwrap();
+        let procfs = Procfs::default();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let tree = loop {
+            let tree = ProcessTree::from_procfs(&procfs).unwrap();
+            if tree.descendant_count(child.id()) == Some(2) || Instant::now() > deadline {
+                break tree;
+            }
+            thread::sleep(WAIT_STEP);
+        };
+
+        let mut sleeps: Vec<u32> = tree.subtree(child.id()).unwrap().children.iter().map(|x| x.value.pid).collect();
+        sleeps.sort_unstable();
+        assert_eq!(sleeps.len(), 2);
+        let signaled = tree.kill_tree(child.id(), Signal::Kill).unwrap();
+        // leaves go first. The shell may exit on its own once they are killed.
+        let mut leaves = signaled[.. 2].to_vec();
+        leaves.sort_unstable();
+        assert_eq!(leaves, sleeps);
+        assert!(signaled.len() == 2 || signaled[2] == child.id());
+        child.wait().unwrap();
+        for pid in sleeps {
+            let sleep = tree.subtree(pid).unwrap();
+            assert!(sleep.value.wait_for_exit(Duration::from_secs(5)).unwrap());
+        }
+        assert!(matches!(tree.kill_tree(0, Signal::Kill), Err(ProcError::Vanished(0))));
+    }
+}
@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use crate::tree;
+use crate::tree::Node;
+use crate::tree::TreeError;
+use crate::user::Names;
+
+use super::ProcError;
+use super::Process;
+use super::Procfs;
+
+/// Resources used by a process and all its descendants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub processes: usize,
+    /// Sum of resident set sizes in bytes.
+    pub rss: u64,
+    /// Sum of user and kernel time.
+    pub cpu: Duration,
+}
+
+/// Processes arranged by their parent pids. Processes which parent is absent, e.g. it has exited
+/// during the listing, are roots like processes with ppid 0.
+pub struct ProcessTree {
+    roots: Vec<Node<Process>>,
+}
+
+impl ProcessTree {
+    /// Fails if pids repeat or parent pids make a cycle which is possible if pids are reused
+    /// while `processes` are read.
+    pub fn new(processes: Vec<Process>) -> Result<Self, TreeError<u32>> {
+        let roots = tree::make_forest(processes, |x| x.pid, |x| x.ppid)?;
+        Ok(ProcessTree { roots })
+    }
+
+    pub fn from_procfs(procfs: &Procfs) -> Result<Self, ProcError> {
+        let processes = procfs.processes()?.collect::<Result<Vec<Process>, _>>()?;
+        Self::new(processes).map_err(|e| ProcError::IncorrectData(format!("Can't build process tree: {}", e)))
+    }
+
+    pub fn roots(&self) -> &[Node<Process>] {
+        &self.roots
+    }
+
+    /// Roots which parent is absent.
+    pub fn orphans(&self) -> impl Iterator<Item=&Process> {
+        self.roots.iter().map(|x| &x.value).filter(|x| x.ppid != 0)
+    }
+
+    /// Nodes from a root down to the process with `pid`.
+    fn path_to(&self, pid: u32) -> Option<Vec<&Node<Process>>> {
+        fn search<'a>(node: &'a Node<Process>, pid: u32, path: &mut Vec<&'a Node<Process>>) -> bool {
+            path.push(node);
+            if node.value.pid == pid || node.children.iter().any(|x| search(x, pid, path)) {
+                return true;
+            }
+            path.pop();
+            false
+        }
+
+        let mut path = vec![];
+        match self.roots.iter().any(|x| search(x, pid, &mut path)) {
+            true => Some(path),
+            false => None,
+        }
+    }
+
+    pub fn subtree(&self, pid: u32) -> Option<&Node<Process>> {
+        self.path_to(pid).and_then(|x| x.last().copied())
+    }
+
+    /// The process with `pid` followed by its parent, grandparent and so on up to a root.
+    pub fn ancestry(&self, pid: u32) -> Option<Vec<&Process>> {
+        self.path_to(pid).map(|x| x.into_iter().rev().map(|x| &x.value).collect())
+    }
+
+    pub fn descendant_count(&self, pid: u32) -> Option<usize> {
+        self.totals(pid).map(|x| x.processes - 1)
+    }
+
+    /// Resources used by the process with `pid` including its descendants.
+    pub fn totals(&self, pid: u32) -> Option<Totals> {
+        let subtree = self.subtree(pid)?;
+        Some(subtree.pre_order().map(|x| &x.value).fold(Totals::default(), |acc, x| Totals {
+            processes: acc.processes + 1,
+            rss: acc.rss + x.vm_rss.unwrap_or(0),
+            cpu: acc.cpu + x.utime + x.stime,
+        }))
+    }
+
+    /// Renders the tree like pstree(1), the subtree of `pid` if it's set. Compact mode omits
+    /// pids and merges identical sibling subtrees into `N*[...]`.
+    pub fn render(&self, pid: Option<u32>, compact: bool) -> Option<String> {
+        self.render_labels(pid, &Labels { compact, names: None })
+    }
+
+    /// Renders the tree like `pstree -u`: the name of the user is shown whenever the effective
+    /// uid of a process differs from the one of its parent.
+    pub fn render_with_users(&self, pid: Option<u32>, compact: bool, names: &Names) -> Option<String> {
+        self.render_labels(pid, &Labels { compact, names: Some(names) })
+    }
+
+    fn render_labels(&self, pid: Option<u32>, labels: &Labels) -> Option<String> {
+        let roots = match pid {
+            Some(pid) => vec![self.subtree(pid)?],
+            None => self.roots.iter().collect(),
+        };
+        let mut lines = vec![];
+        for group in render_children(&roots, None, labels) {
+            lines.extend(group);
+        }
+        Some(lines.join("\n"))
+    }
+}
+
+/// What labels of processes show.
+struct Labels<'a> {
+    /// Omit pids.
+    compact: bool,
+    /// Show users on uid transitions.
+    names: Option<&'a Names>,
+}
+
+impl Labels<'_> {
+    /// Label of `p` which parent has `parent_uid`. Roots have no parent.
+    fn label(&self, p: &Process, parent_uid: Option<u32>) -> String {
+        let user = self.names
+            .filter(|_| parent_uid.is_some_and(|x| x != p.uid.effective))
+            .map(|x| x.user_or_id(p.uid.effective));
+        match (self.compact, user) {
+            (true, None) => p.name.clone(),
+            (true, Some(user)) => format!("{}({})", p.name, user),
+            (false, None) => format!("{}({})", p.name, p.pid),
+            (false, Some(user)) => format!("{}({},{})", p.name, p.pid, user),
+        }
+    }
+}
+
+/// Renders every child. Identical adjacent subtrees are merged in compact mode.
+fn render_children(children: &[&Node<Process>], parent_uid: Option<u32>, labels: &Labels) -> Vec<Vec<String>> {
+    let mut children = children.to_vec();
+    children.sort_by(|x, y| (&x.value.name, x.value.pid).cmp(&(&y.value.name, y.value.pid)));
+    let mut groups: Vec<(usize, Vec<String>)> = vec![];
+    for child in children {
+        let lines = render_node(child, parent_uid, labels);
+        match groups.last_mut() {
+            Some((count, last)) if labels.compact && *last == lines => *count += 1,
+            _ => groups.push((1, lines)),
+        }
+    }
+
+    groups.into_iter().map(|(count, mut lines)| {
+        if count > 1 {
+            let prefix = format!("{}*[", count);
+            let indent = " ".repeat(prefix.chars().count());
+            for (i, line) in lines.iter_mut().enumerate() {
+                let head = if i == 0 { &prefix } else { &indent };
+                *line = format!("{}{}", head, line);
+            }
+            if let Some(x) = lines.last_mut() {
+                x.push(']');
+            }
+        }
+        lines
+    }).collect()
+}
+
+fn render_node(node: &Node<Process>, parent_uid: Option<u32>, labels: &Labels) -> Vec<String> {
+    let label = labels.label(&node.value, parent_uid);
+    let children: Vec<&Node<Process>> = node.children.iter().collect();
+    let groups = render_children(&children, Some(node.value.uid.effective), labels);
+    if groups.is_empty() {
+        return vec![label];
+    }
+
+    let indent = " ".repeat(label.chars().count());
+    let last = groups.len() - 1;
+    let mut lines = vec![];
+    for (i, group) in groups.into_iter().enumerate() {
+        // connectors of the first line of a child and of the rest of its lines.
+        let (first, rest) = match (i, last) {
+            (0, 0) => ("───", "   "),
+            (0, _) => ("─┬─", " │ "),
+            (i, last) if i == last => (" └─", "   "),
+            _ => (" ├─", " │ "),
+        };
+        for (j, line) in group.into_iter().enumerate() {
+            let head = if i == 0 && j == 0 { &label } else { &indent };
+            let connector = if j == 0 { first } else { rest };
+            lines.push(format!("{}{}{}", head, connector, line));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(name: &str, pid: u32, ppid: u32, rss: u64, cpu: u64) -> Process {
+        Process {
+            name: name.to_owned(),
+            pid,
+            ppid,
+            vm_rss: Some(rss),
+            utime: Duration::from_secs(cpu),
+            ..Process::default()
+        }
+    }
+
+    fn processes() -> Vec<Process> {
+        vec![
+            process("init", 1, 0, 10, 1),
+            process("getty", 30, 1, 1, 0),
+            process("getty", 31, 1, 1, 0),
+            process("sshd", 20, 1, 5, 2),
+            process("sshd", 21, 20, 3, 0),
+            process("bash", 22, 21, 4, 3),
+            process("vim", 23, 22, 8, 4),
+            process("kthreadd", 2, 0, 0, 0),
+            // the parent exited.
+            process("orphan", 40, 39, 2, 0),
+        ]
+    }
+
+    #[test]
+    fn lookup() {
+        let tree = ProcessTree::new(processes()).unwrap();
+        let roots: Vec<u32> = tree.roots().iter().map(|x| x.value.pid).collect();
+        assert_eq!(roots, vec![1, 2, 40]);
+        assert_eq!(tree.orphans().map(|x| x.pid).collect::<Vec<u32>>(), vec![40]);
+
+        assert_eq!(tree.subtree(21).unwrap().children[0].value.name, "bash");
+        assert!(tree.subtree(99).is_none());
+        let ancestry: Vec<u32> = tree.ancestry(23).unwrap().iter().map(|x| x.pid).collect();
+        assert_eq!(ancestry, vec![23, 22, 21, 20, 1]);
+        assert!(tree.ancestry(99).is_none());
+
+        assert_eq!(tree.descendant_count(1), Some(6));
+        assert_eq!(tree.descendant_count(23), Some(0));
+        assert_eq!(tree.totals(20), Some(Totals { processes: 4, rss: 20, cpu: Duration::from_secs(9) }));
+    }
+
+    #[test]
+    fn reused_pids() {
+        let cycle = vec![process("a", 1, 2, 0, 0), process("b", 2, 1, 0, 0)];
+        assert!(matches!(ProcessTree::new(cycle), Err(TreeError::Cycle(_))));
+    }
+
+    #[test]
+    fn render() {
+        let tree = ProcessTree::new(processes()).unwrap();
+        let expected = "\
+init(1)─┬─getty(30)
+        ├─getty(31)
+        └─sshd(20)───sshd(21)───bash(22)───vim(23)
+kthreadd(2)
+orphan(40)";
+        assert_eq!(tree.render(None, false).unwrap(), expected);
+
+        let expected = "\
+init─┬─2*[getty]
+     └─sshd───sshd───bash───vim
+kthreadd
+orphan";
+        assert_eq!(tree.render(None, true).unwrap(), expected);
+        assert_eq!(tree.render(Some(22), true).unwrap(), "bash───vim");
+        assert!(tree.render(Some(99), true).is_none());
+    }
+
+    #[test]
+    fn render_merged_subtrees() {
+        let tree = ProcessTree::new(vec![
+            process("sh", 1, 0, 0, 0),
+            process("make", 2, 1, 0, 0),
+            process("cc", 3, 2, 0, 0),
+            process("ld", 4, 2, 0, 0),
+            process("make", 5, 1, 0, 0),
+            process("cc", 6, 5, 0, 0),
+            process("ld", 7, 5, 0, 0),
+        ]).unwrap();
+        let expected = "\
+sh───2*[make─┬─cc
+             └─ld]";
+        assert_eq!(tree.render(None, true).unwrap(), expected);
+    }
+
+    #[test]
+    fn render_users() {
+        let mut processes = processes();
+        // bash and vim of pid 1000 which has no name.
+        for x in processes.iter_mut().filter(|x| x.pid == 22 || x.pid == 23) {
+            x.uid.effective = 1000;
+        }
+        let tree = ProcessTree::new(processes).unwrap();
+        let names = Names::default();
+        assert_eq!(tree.render_with_users(Some(20), false, &names).unwrap(), "sshd(20)───sshd(21)───bash(22,1000)───vim(23)");
+        assert_eq!(tree.render_with_users(Some(22), true, &names).unwrap(), "bash───vim");
+        assert_eq!(tree.render_with_users(Some(21), true, &names).unwrap(), "sshd───bash(1000)───vim");
+    }
+}
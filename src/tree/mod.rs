@@ -1,3 +1,8 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::Hash;
+
 use crate::utils::vector::move_to_end_by;
 
 pub struct Node<T> {
@@ -14,6 +19,177 @@ impl<T> Node<T> {
             children: vec!(),
         }
     }
+
+    /// Visits a node before its children.
+    pub fn pre_order(&self) -> PreOrder<'_, T> {
+        PreOrder { stack: vec![self] }
+    }
+
+    /// Visits a node after its children.
+    pub fn post_order(&self) -> PostOrder<'_, T> {
+        PostOrder { stack: vec![(self, 0)] }
+    }
+
+    /// Visits nodes level by level from the top.
+    pub fn level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder { queue: vec![self].into() }
+    }
+}
+
+pub struct PreOrder<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for PreOrder<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+pub struct PostOrder<'a, T> {
+    // a node and the number of its children visited.
+    stack: Vec<(&'a Node<T>, usize)>,
+}
+
+impl<'a, T> Iterator for PostOrder<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, visited) = self.stack.last_mut()?;
+            let node = *node;
+            match node.children.get(*visited) {
+                Some(child) => {
+                    *visited += 1;
+                    self.stack.push((child, 0));
+                },
+                None => {
+                    self.stack.pop();
+                    return Some(node);
+                },
+            }
+        }
+    }
+}
+
+pub struct LevelOrder<'a, T> {
+    queue: VecDeque<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children.iter());
+        Some(node)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TreeError<K> {
+    DuplicateKey(K),
+    /// Keys of items each of which is a parent of the next one, the last is the parent of the
+    /// first.
+    Cycle(Vec<K>),
+}
+
+impl<K: fmt::Debug> fmt::Display for TreeError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::DuplicateKey(key) => write!(f, "duplicate key {:?}", key),
+            TreeError::Cycle(keys) => write!(f, "cycle of keys {:?}", keys),
+        }
+    }
+}
+
+/// Builds trees in linear time. An item is a child of the item which key equals its parent key.
+/// Items which parent is absent are roots. Children keep the order of `items`.
+///
+/// # Examples
+///
+/// ```
+/// let forest = make_forest(processes, |x| x.pid, |x| x.ppid)?;
+/// ```
+pub fn make_forest<T, K, F, P>(items: Vec<T>, key: F, parent_key: P) -> Result<Vec<Node<T>>, TreeError<K>>
+where
+    K: Hash + Eq,
+    F: Fn(&T) -> K,
+    P: Fn(&T) -> K,
+{
+    let mut index = HashMap::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        if let Some(x) = index.insert(key(item), i) {
+            return Err(TreeError::DuplicateKey(key(&items[x])));
+        }
+    }
+
+    let parents: Vec<Option<usize>> = items.iter().map(|x| index.get(&parent_key(x)).copied()).collect();
+    // children are linked in lists to avoid a vector per item.
+    let mut first_child: Vec<Option<usize>> = vec![None; items.len()];
+    let mut next_sibling: Vec<Option<usize>> = vec![None; items.len()];
+    for i in (0 .. items.len()).rev() {
+        if let Some(parent) = parents[i] {
+            next_sibling[i] = first_child[parent];
+            first_child[parent] = Some(i);
+        }
+    }
+
+    let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    let mut forest = vec![];
+    // built subtrees, the last ones are children of the item being finished.
+    let mut built: Vec<Node<T>> = vec![];
+    let mut stack = vec![];
+    for root in (0 .. items.len()).filter(|x| parents[*x].is_none()) {
+        stack.push((root, false));
+        while let Some((i, is_expanded)) = stack.pop() {
+            if !is_expanded {
+                stack.push((i, true));
+                let mut child = first_child[i];
+                let since = stack.len();
+                while let Some(x) = child {
+                    stack.push((x, false));
+                    child = next_sibling[x];
+                }
+                stack[since ..].reverse();
+                continue;
+            }
+
+            let count = std::iter::successors(first_child[i], |x| next_sibling[*x]).count();
+            let mut node = Node::new(items[i].take().unwrap());
+            node.children = built.split_off(built.len() - count);
+            built.push(node);
+        }
+        forest.append(&mut built);
+    }
+
+    // items left are unreachable from roots, so each of them is in a cycle or under it.
+    match items.iter().position(|x| x.is_some()) {
+        Some(start) => {
+            let mut seen = vec![false; items.len()];
+            let mut i = start;
+            while !seen[i] {
+                seen[i] = true;
+                i = parents[i].unwrap();
+            }
+
+            let mut cycle = vec![];
+            let first = i;
+            loop {
+                cycle.push(key(items[i].as_ref().unwrap()));
+                i = parents[i].unwrap();
+                if i == first {
+                    break;
+                }
+            }
+            Err(TreeError::Cycle(cycle))
+        },
+        None => Ok(forest),
+    }
 }
 
 
@@ -110,4 +286,79 @@ mod tests {
         assert_eq!(vim.value.ppid, 3, "Wrong children process");
         assert_eq!(vim.value.pid, 125, "Wrong children process");
     }
+
+    fn numbers() -> Node<u32> {
+        let mut root = Node::new(1);
+        let mut two = Node::new(2);
+        two.children = vec![Node::new(4), Node::new(5)];
+        let mut three = Node::new(3);
+        three.children = vec![Node::new(6)];
+        root.children = vec![two, three];
+        root
+    }
+
+    #[test]
+    fn orders() {
+        let root = numbers();
+        assert_eq!(root.pre_order().map(|x| x.value).collect::<Vec<_>>(), vec![1, 2, 4, 5, 3, 6]);
+        assert_eq!(root.post_order().map(|x| x.value).collect::<Vec<_>>(), vec![4, 5, 2, 6, 3, 1]);
+        assert_eq!(root.level_order().map(|x| x.value).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+        let leaf = Node::new(7);
+        assert_eq!(leaf.post_order().count(), 1);
+    }
+
+    fn process(name: &str, pid: u32, ppid: u32) -> Process {
+        Process { name: name.to_owned(), pid, ppid }
+    }
+
+    #[test]
+    fn forest() {
+        let processes = vec![
+            process("vim", 125, 3),
+            process("init", 1, 0),
+            process("kde", 3, 1),
+            process("bash", 333, 1),
+            process("ssh", 412, 2),
+            process("systemd", 2, 0),
+            // the parent is absent.
+            process("orphan", 40, 39),
+        ];
+        let forest = make_forest(processes, |x| x.pid, |x| x.ppid).unwrap();
+        let roots: Vec<u32> = forest.iter().map(|x| x.value.pid).collect();
+        assert_eq!(roots, vec![1, 2, 40]);
+
+        let init: Vec<u32> = forest[0].pre_order().map(|x| x.value.pid).collect();
+        assert_eq!(init, vec![1, 3, 125, 333]);
+        assert_eq!(forest[1].children[0].value.name, "ssh");
+        assert!(forest[2].children.is_empty());
+    }
+
+    #[test]
+    fn forest_errors() {
+        let processes = vec![process("a", 1, 0), process("b", 1, 0)];
+        assert_eq!(make_forest(processes, |x| x.pid, |x| x.ppid).err(), Some(TreeError::DuplicateKey(1)));
+
+        let processes = vec![
+            process("init", 1, 0),
+            process("a", 2, 4),
+            process("b", 3, 2),
+            process("c", 4, 3),
+            process("under_cycle", 5, 3),
+        ];
+        let cycle = make_forest(processes, |x| x.pid, |x| x.ppid).err();
+        assert!(matches!(&cycle, Some(TreeError::Cycle(x)) if x.len() == 3), "{:?}", cycle);
+
+        let processes = vec![process("self", 1, 1)];
+        assert_eq!(make_forest(processes, |x| x.pid, |x| x.ppid).err(), Some(TreeError::Cycle(vec![1])));
+    }
+
+    #[test]
+    fn deep_forest() {
+        // the builder and the iterators don't recurse.
+        let chain: Vec<(u32, u32)> = (1 .. 10_000).map(|x| (x, x - 1)).collect();
+        let forest = make_forest(chain, |x| x.0, |x| x.1).unwrap();
+        assert_eq!(forest.len(), 1);
+        assert_eq!(forest[0].post_order().count(), 9_999);
+        assert_eq!(forest[0].level_order().last().map(|x| x.value.0), Some(9_999));
+    }
 }
@@ -0,0 +1,471 @@
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::Group;
+use super::GroupData;
+use super::Passwd;
+use super::PasswdError;
+use super::UserData;
+
+/// Name of the lock file in `etc` like the one of `lckpwdf`.
+const LOCK_NAME: &str = ".pwd.lock";
+const DEFAULT_MODE: u32 = 0o644;
+// the version of libc in use doesn't have it. Every Linux architecture has the same number.
+const F_OFD_SETLK: libc::c_int = 37;
+
+/// Lock of the databases. Like `lckpwdf` it's a write lock of the whole lock file which stays
+/// empty and is never removed, so the lock is released by the kernel when the owner exits. The
+/// lock belongs to the open file rather than to the process, so it excludes edits of the same
+/// process too.
+struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Fails with `Locked` if the lock is held already. Unlike `lckpwdf` it doesn't wait.
+    fn acquire(path: &Path) -> Result<Self, PasswdError> {
+        let file = OpenOptions::new().write(true).create(true).truncate(false).mode(0o600).open(path)?;
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = libc::F_WRLCK as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        if unsafe { libc::fcntl(file.as_raw_fd(), F_OFD_SETLK, &lock) } == -1 {
+            let e = io::Error::last_os_error();
+            return match e.raw_os_error() {
+                Some(libc::EAGAIN) | Some(libc::EACCES) => Err(PasswdError::Locked),
+                _ => Err(e.into()),
+            };
+        }
+        Ok(Lock { _file: file })
+    }
+}
+
+/// `path` with `suffix` appended to the file name like `passwd+` or `passwd-`.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Writes `content` to a temporary file next to `path` which is synced and can be renamed over
+/// `path`. The mode of `path` is kept.
+fn stage(path: &Path, content: &str) -> Result<PathBuf, PasswdError> {
+    let mode = match fs::metadata(path) {
+        Ok(x) => x.permissions().mode() & 0o7777,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => DEFAULT_MODE,
+        Err(e) => return Err(e.into()),
+    };
+
+    let temp = suffixed(path, "+");
+    // the lock is held, so the file can only be left by a failed edit.
+    let _ = fs::remove_file(&temp);
+    let mut file = OpenOptions::new().write(true).create_new(true).mode(mode).open(&temp)?;
+    let res = file.write_all(content.as_bytes())
+        .and_then(|_| file.set_permissions(fs::Permissions::from_mode(mode)))
+        .and_then(|_| file.sync_all());
+    if let Err(e) = res {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(temp)
+}
+
+/// Keeps the current `path` as `path-` like shadow-utils does. `None` if there is no `path`.
+fn backup(path: &Path) -> io::Result<Option<PathBuf>> {
+    let backup = suffixed(path, "-");
+    match fs::remove_file(&backup) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        res => res?,
+    }
+    match fs::hard_link(path, &backup) {
+        Ok(()) => Ok(Some(backup)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Puts back the file `backup` has kept or removes `path` if there was no file.
+fn restore(path: &Path, backup: &Option<PathBuf>) {
+    let _ = match backup {
+        Some(backup) => fs::rename(backup, path),
+        None => fs::remove_file(path),
+    };
+}
+
+/// Replaces every file with its content, so readers see either the old or the new file. All the
+/// files are written and the old ones are kept as backups before any of them is replaced. If one
+/// can't be replaced, the files replaced before it are restored from their backups.
+fn write_atomically(files: &[(PathBuf, String)]) -> Result<(), PasswdError> {
+    let mut staged = vec![];
+    let mut backups = vec![];
+    let res = files.iter().try_for_each(|(path, content)| {
+        staged.push(stage(path, content)?);
+        backups.push(backup(path)?);
+        Ok(())
+    });
+    if let Err(e) = res {
+        staged.iter().for_each(|x| { let _ = fs::remove_file(x); });
+        return Err(e);
+    }
+
+    for (i, (temp, (path, _))) in staged.iter().zip(files).enumerate() {
+        if let Err(e) = fs::rename(temp, path) {
+            staged[i ..].iter().for_each(|x| { let _ = fs::remove_file(x); });
+            files[.. i].iter().zip(&backups).for_each(|((path, _), backup)| restore(path, backup));
+            return Err(e.into());
+        }
+    }
+
+    // the renames are durable only after the directories are synced. The edit is done already if
+    // it fails.
+    let mut dirs: Vec<&Path> = files.iter().filter_map(|(path, _)| path.parent()).collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn join_lines<T: ToString>(entries: &[T]) -> String {
+    entries.iter().map(|x| x.to_string() + "\n").collect()
+}
+
+/// Checks that `value` can be stored in a field. Names can't contain commas either because of
+/// member lists.
+fn check_field(field: &str, value: &str, is_name: bool) -> Result<(), PasswdError> {
+    let invalid = |reason: &str| Err(PasswdError::InvalidField(format!("{} `{}` {}", field, value, reason)));
+    if is_name && value.is_empty() {
+        return invalid("is empty");
+    }
+    if is_name && (value.starts_with('-') || value.contains(',')) {
+        return invalid("is not a valid name");
+    }
+    if value.contains(':') || value.contains('\n') {
+        return invalid("contains `:` or a line break");
+    }
+    Ok(())
+}
+
+fn check_user(user: &UserData) -> Result<(), PasswdError> {
+    check_field("name", &user.name, true)?;
+    check_field("password", &user.passwd, false)?;
+    check_field("comment", &user.comment, false)?;
+    check_field("home directory", &user.pw_dir, false)?;
+    check_field("shell", &user.pw_shell, false)
+}
+
+fn check_group(group: &GroupData) -> Result<(), PasswdError> {
+    check_field("name", &group.name, true)?;
+    check_field("password", &group.passwd, false)?;
+    for x in &group.members {
+        check_field("member", x, true)?;
+    }
+    Ok(())
+}
+
+/// Position of the entry named `name`.
+fn position<T, F: Fn(&T) -> &str>(entries: &[T], name: &str, key: F) -> Result<usize, PasswdError> {
+    entries.iter().position(|x| key(x) == name).ok_or(PasswdError::DoesNotExist)
+}
+
+/// Editor of the passwd and group databases of a system rooted at `root` like `useradd`,
+/// `usermod` and `userdel`. Every edit holds `etc/.pwd.lock` and checks that names and ids are
+/// unique. Absent databases are considered empty.
+pub struct Accounts {
+    root: PathBuf,
+}
+
+impl Accounts {
+    /// Edits `root/etc/passwd` and `root/etc/group`, e.g. the ones of the system if `root` is `/`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Accounts { root: root.as_ref().to_path_buf() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join("etc").join(name)
+    }
+
+    fn lock(&self) -> Result<Lock, PasswdError> {
+        Lock::acquire(&self.path(LOCK_NAME))
+    }
+
+    pub fn users(&self) -> Result<Vec<UserData>, PasswdError> {
+        let path = self.path("passwd");
+        match path.exists() {
+            true => Passwd::with_path(path).collect(),
+            false => Ok(vec![]),
+        }
+    }
+
+    pub fn groups(&self) -> Result<Vec<GroupData>, PasswdError> {
+        let path = self.path("group");
+        match path.exists() {
+            true => Group::with_path(path).collect(),
+            false => Ok(vec![]),
+        }
+    }
+
+    fn write_users(&self, users: &[UserData]) -> Result<(), PasswdError> {
+        write_atomically(&[(self.path("passwd"), join_lines(users))])
+    }
+
+    fn write_groups(&self, groups: &[GroupData]) -> Result<(), PasswdError> {
+        write_atomically(&[(self.path("group"), join_lines(groups))])
+    }
+
+    /// Writes both databases which are edited together, passwd is replaced first.
+    fn write_both(&self, users: &[UserData], groups: &[GroupData]) -> Result<(), PasswdError> {
+        write_atomically(&[(self.path("passwd"), join_lines(users)), (self.path("group"), join_lines(groups))])
+    }
+
+    /// Fails if another user except the one at `skip` has the name or the uid of `user`.
+    fn check_unique_user(users: &[UserData], user: &UserData, skip: Option<usize>) -> Result<(), PasswdError> {
+        for (i, x) in users.iter().enumerate().filter(|(i, _)| Some(*i) != skip) {
+            if x.name == user.name {
+                return Err(PasswdError::Conflict(format!("user `{}` already exists", x.name)));
+            }
+            if x.user_id == user.user_id {
+                return Err(PasswdError::Conflict(format!("uid {} is taken by `{}`", x.user_id, x.name)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn useradd(&self, user: UserData) -> Result<(), PasswdError> {
+        check_user(&user)?;
+        let _lock = self.lock()?;
+        let mut users = self.users()?;
+        Self::check_unique_user(&users, &user, None)?;
+        users.push(user);
+        self.write_users(&users)
+    }
+
+    /// Replaces the entry of the user `name` with `user`. If the user is renamed, group member
+    /// lists are updated too.
+    pub fn usermod(&self, name: &str, user: UserData) -> Result<(), PasswdError> {
+        check_user(&user)?;
+        let _lock = self.lock()?;
+        let mut users = self.users()?;
+        let i = position(&users, name, |x| &x.name)?;
+        Self::check_unique_user(&users, &user, Some(i))?;
+
+        if user.name == name {
+            users[i] = user;
+            return self.write_users(&users);
+        }
+
+        let mut groups = self.groups()?;
+        for x in groups.iter_mut().flat_map(|x| x.members.iter_mut()).filter(|x| *x == name) {
+            *x = user.name.clone();
+        }
+        users[i] = user;
+        self.write_both(&users, &groups)
+    }
+
+    /// Makes the user `name` a member of exactly `groups` besides its primary group like
+    /// `usermod -G`.
+    pub fn set_groups(&self, name: &str, groups: &[&str]) -> Result<(), PasswdError> {
+        let _lock = self.lock()?;
+        position(&self.users()?, name, |x| &x.name)?;
+        let mut all = self.groups()?;
+        for x in groups {
+            position(&all, x, |x| &x.name)?;
+        }
+
+        for group in &mut all {
+            let is_member = group.members.iter().any(|x| x == name);
+            match (is_member, groups.contains(&group.name.as_str())) {
+                (false, true) => group.members.push(name.to_owned()),
+                (true, false) => group.members.retain(|x| x != name),
+                _ => (),
+            }
+        }
+        self.write_groups(&all)
+    }
+
+    /// Removes the user `name` and its memberships. The group of the same name which is its
+    /// primary group is removed too unless it has members.
+    pub fn userdel(&self, name: &str) -> Result<(), PasswdError> {
+        let _lock = self.lock()?;
+        let mut users = self.users()?;
+        let user = users.remove(position(&users, name, |x| &x.name)?);
+
+        let mut groups = self.groups()?;
+        for group in &mut groups {
+            group.members.retain(|x| x != name);
+        }
+        groups.retain(|x| !(x.name == user.name && x.group_id == user.group_id && x.members.is_empty()));
+        self.write_both(&users, &groups)
+    }
+
+    pub fn groupadd(&self, group: GroupData) -> Result<(), PasswdError> {
+        check_group(&group)?;
+        let _lock = self.lock()?;
+        let mut groups = self.groups()?;
+        for x in &groups {
+            if x.name == group.name {
+                return Err(PasswdError::Conflict(format!("group `{}` already exists", x.name)));
+            }
+            if x.group_id == group.group_id {
+                return Err(PasswdError::Conflict(format!("gid {} is taken by `{}`", x.group_id, x.name)));
+            }
+        }
+        groups.push(group);
+        self.write_groups(&groups)
+    }
+
+    /// Removes the group `name`. Fails if it's the primary group of a user like `groupdel`.
+    pub fn groupdel(&self, name: &str) -> Result<(), PasswdError> {
+        let _lock = self.lock()?;
+        let mut groups = self.groups()?;
+        let group = groups.remove(position(&groups, name, |x| &x.name)?);
+        if let Some(user) = self.users()?.iter().find(|x| x.group_id == group.group_id) {
+            return Err(PasswdError::Conflict(format!("group `{}` is the primary group of `{}`", name, user.name)));
+        }
+        self.write_groups(&groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Root directory with `etc` in the temporary directory removed on drop.
+    struct Root(PathBuf);
+
+    impl Root {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("accounts_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("etc")).unwrap();
+            Root(path)
+        }
+
+        fn read(&self, name: &str) -> String {
+            fs::read_to_string(self.0.join("etc").join(name)).unwrap()
+        }
+    }
+
+    impl Drop for Root {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn user(name: &str, id: u32) -> UserData {
+        UserData {
+            name: name.to_owned(),
+            passwd: "x".to_owned(),
+            user_id: id,
+            group_id: id,
+            comment: String::new(),
+            pw_dir: format!("/home/{}", name),
+            pw_shell: "/bin/sh".to_owned(),
+        }
+    }
+
+    fn group(name: &str, id: u32) -> GroupData {
+        GroupData { name: name.to_owned(), passwd: "x".to_owned(), group_id: id, members: vec![] }
+    }
+
+    #[test]
+    fn add_and_modify() {
+        let root = Root::new("add");
+        let accounts = Accounts::new(&root.0);
+        accounts.useradd(user("alice", 1000)).unwrap();
+        accounts.useradd(user("bob", 1001)).unwrap();
+        accounts.groupadd(group("alice", 1000)).unwrap();
+        accounts.groupadd(group("sudo", 27)).unwrap();
+        accounts.groupadd(group("users", 100)).unwrap();
+        assert_eq!(root.read("passwd"), "alice:x:1000:1000::/home/alice:/bin/sh\nbob:x:1001:1001::/home/bob:/bin/sh\n");
+
+        assert!(matches!(accounts.useradd(user("alice", 1002)), Err(PasswdError::Conflict(_))));
+        assert!(matches!(accounts.useradd(user("carol", 1000)), Err(PasswdError::Conflict(_))));
+        assert!(matches!(accounts.groupadd(group("wheel", 27)), Err(PasswdError::Conflict(_))));
+        assert!(matches!(accounts.useradd(user("a:b", 1002)), Err(PasswdError::InvalidField(_))));
+        assert!(matches!(accounts.useradd(user("", 1002)), Err(PasswdError::InvalidField(_))));
+
+        accounts.set_groups("alice", &["sudo", "users"]).unwrap();
+        accounts.set_groups("bob", &["users"]).unwrap();
+        accounts.set_groups("alice", &["users"]).unwrap();
+        assert!(matches!(accounts.set_groups("alice", &["wheel"]), Err(PasswdError::DoesNotExist)));
+        assert_eq!(root.read("group"), "alice:x:1000:\nsudo:x:27:\nusers:x:100:alice,bob\n");
+
+        let mut renamed = user("alicia", 1000);
+        renamed.pw_shell = "/bin/zsh".to_owned();
+        let (passwd, group) = (root.read("passwd"), root.read("group"));
+        accounts.usermod("alice", renamed.clone()).unwrap();
+        // the previous databases are kept.
+        assert_eq!((root.read("passwd-"), root.read("group-")), (passwd, group));
+        assert_eq!(accounts.users().unwrap()[0], renamed);
+        assert_eq!(accounts.groups().unwrap()[2].members, vec!["alicia", "bob"]);
+        // keeping its own uid is fine but taking another one is not.
+        assert!(matches!(accounts.usermod("bob", user("bob", 1000)), Err(PasswdError::Conflict(_))));
+        assert!(matches!(accounts.usermod("alice", user("alice", 1000)), Err(PasswdError::DoesNotExist)));
+    }
+
+    #[test]
+    fn delete() {
+        let root = Root::new("delete");
+        let accounts = Accounts::new(&root.0);
+        accounts.useradd(user("alice", 1000)).unwrap();
+        accounts.useradd(user("bob", 1001)).unwrap();
+        accounts.groupadd(group("alice", 1000)).unwrap();
+        accounts.groupadd(group("bob", 1001)).unwrap();
+        accounts.groupadd(group("users", 100)).unwrap();
+        accounts.set_groups("alice", &["users"]).unwrap();
+
+        assert!(matches!(accounts.groupdel("bob"), Err(PasswdError::Conflict(_))));
+        accounts.userdel("alice").unwrap();
+        assert!(matches!(accounts.userdel("alice"), Err(PasswdError::DoesNotExist)));
+        assert_eq!(root.read("passwd"), "bob:x:1001:1001::/home/bob:/bin/sh\n");
+        assert_eq!(root.read("group"), "bob:x:1001:\nusers:x:100:\n");
+        accounts.groupdel("users").unwrap();
+        assert_eq!(root.read("group"), "bob:x:1001:\n");
+    }
+
+    #[test]
+    fn lock_and_mode() {
+        let root = Root::new("lock");
+        let accounts = Accounts::new(&root.0);
+        let passwd = root.0.join("etc/passwd");
+        fs::write(&passwd, "root:x:0:0:root:/root:/bin/sh\n").unwrap();
+        fs::set_permissions(&passwd, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let lock = accounts.lock().unwrap();
+        assert!(matches!(accounts.useradd(user("alice", 1000)), Err(PasswdError::Locked)));
+        drop(lock);
+        accounts.useradd(user("alice", 1000)).unwrap();
+        assert_eq!(fs::metadata(&passwd).unwrap().permissions().mode() & 0o7777, 0o640);
+        assert!(!root.0.join("etc/passwd+").exists());
+        // the lock file stays like the one of `lckpwdf`, only the lock is released.
+        assert_eq!(fs::read(root.0.join("etc").join(LOCK_NAME)).unwrap(), b"");
+        let lock = accounts.lock().unwrap();
+        assert!(matches!(accounts.lock(), Err(PasswdError::Locked)));
+        drop(lock);
+        accounts.userdel("alice").unwrap();
+        assert_eq!(root.read("passwd"), "root:x:0:0:root:/root:/bin/sh\n");
+
+        // a file which can't be written leaves both databases as they were.
+        accounts.useradd(user("alice", 1000)).unwrap();
+        accounts.groupadd(GroupData { members: vec!["alice".to_owned()], ..group("users", 100) }).unwrap();
+        let (passwd, group) = (root.read("passwd"), root.read("group"));
+        fs::create_dir(root.0.join("etc/group+")).unwrap();
+        assert!(accounts.usermod("alice", user("alicia", 1000)).is_err());
+        assert!(accounts.userdel("alice").is_err());
+        assert_eq!((root.read("passwd"), root.read("group")), (passwd, group));
+        assert!(!root.0.join("etc/passwd+").exists());
+        fs::remove_dir(root.0.join("etc/group+")).unwrap();
+
+        fs::write(root.0.join("etc/passwd"), "broken\n").unwrap();
+        assert!(matches!(accounts.useradd(user("alice", 1000)), Err(PasswdError::IncorrectData(1, _))));
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::path::Path;
+
+use super::Entries;
+use super::PasswdError;
+use super::UserData;
+
+const GROUP_PATH: &str = "/etc/group";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupData {
+    pub name: String,
+    pub passwd: String,
+    pub group_id: u32,
+    /// Names of users which have the group as a supplementary one.
+    pub members: Vec<String>,
+}
+
+impl fmt::Display for GroupData {
+    /// Formats the entry as a line of the database.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.name, self.passwd, self.group_id, self.members.join(","))
+    }
+}
+
+type GroupDataResult = Result<GroupData, PasswdError>;
+
+/// Cursor over a group database like `setgrent`, `getgrent` and `endgrent` of libc.
+pub struct Group {
+    entries: Entries,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self::with_path(GROUP_PATH)
+    }
+}
+
+impl Group {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the database from `path` instead of `/etc/group`.
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        Group { entries: Entries::new(path.as_ref()) }
+    }
+
+    /// Rewinds to the first entry.
+    pub fn setgrent(&mut self) -> Result<(), PasswdError> {
+        self.entries.rewind()
+    }
+
+    pub fn endgrent(&mut self) {
+        self.entries.close()
+    }
+
+    /// Returns the next entry. Blank lines are skipped.
+    pub fn getgrent(&mut self) -> Option<GroupDataResult> {
+        self.entries.next(parse_group_data)
+    }
+
+    pub fn getgrnam(&mut self, name: &str) -> GroupDataResult {
+        self.entries.find(parse_group_data, |x| x.name == name)
+    }
+
+    pub fn getgrgid(&mut self, id: u32) -> GroupDataResult {
+        self.entries.find(parse_group_data, |x| x.group_id == id)
+    }
+
+    /// Ids of groups of `user` like `getgrouplist`: the primary group goes first followed by
+    /// groups listing the user as a member in the order of the database. Ids don't repeat.
+    pub fn getgrouplist(&mut self, user: &UserData) -> Result<Vec<u32>, PasswdError> {
+        self.setgrent()?;
+        let mut groups = vec![user.group_id];
+        while let Some(group) = self.getgrent() {
+            let group = group?;
+            if group.members.contains(&user.name) && !groups.contains(&group.group_id) {
+                groups.push(group.group_id);
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl Iterator for Group {
+    type Item = GroupDataResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.getgrent()
+    }
+}
+
+/// Parses exactly four fields. Members are separated by commas.
+fn parse_group_data(data: &[&str]) -> Option<GroupData> {
+    match *data {
+        [name, passwd, group_id, members] if !name.is_empty() => Some(GroupData {
+            name: name.to_owned(),
+            passwd: passwd.to_owned(),
+            group_id: group_id.parse().ok()?,
+            members: members.split(',').filter(|x| !x.is_empty()).map(str::to_owned).collect(),
+        }),
+        _ => None,
+    }
+}
+
+pub fn getgrnam(name: &str) -> GroupDataResult {
+    Group::new().getgrnam(name)
+}
+
+pub fn getgrgid(id: u32) -> GroupDataResult {
+    Group::new().getgrgid(id)
+}
+
+pub fn getgrouplist(user: &UserData) -> Result<Vec<u32>, PasswdError> {
+    Group::new().getgrouplist(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::PasswdFile;
+
+    const GROUP: &str = "root:x:0:
+adm:x:4:syslog,alice
+sudo:x:27:alice
+
+alice:x:1000:
+users:x:100:bob,alice
+";
+
+    fn user(name: &str, group_id: u32) -> UserData {
+        UserData {
+            name: name.to_owned(),
+            passwd: "x".to_owned(),
+            user_id: group_id,
+            group_id,
+            comment: String::new(),
+            pw_dir: String::new(),
+            pw_shell: String::new(),
+        }
+    }
+
+    #[test]
+    fn cursor() {
+        let file = PasswdFile::new("group", GROUP);
+        let mut group = Group::with_path(&file.0);
+        let names: Vec<String> = group.by_ref().map(|x| x.unwrap().name).collect();
+        assert_eq!(names, vec!["root", "adm", "sudo", "alice", "users"]);
+
+        assert_eq!(group.getgrnam("adm").unwrap(), GroupData {
+            name: "adm".to_owned(),
+            passwd: "x".to_owned(),
+            group_id: 4,
+            members: vec!["syslog".to_owned(), "alice".to_owned()],
+        });
+        assert!(group.getgrgid(0).unwrap().members.is_empty());
+        assert!(matches!(group.getgrgid(5), Err(PasswdError::DoesNotExist)));
+
+        assert!(parse_group_data(&["a", "x", "1", "b,,c"]).is_some_and(|x| x.members == ["b", "c"]));
+        assert!(parse_group_data(&["a", "x", "1"]).is_none());
+        assert!(parse_group_data(&["a", "x", "g", ""]).is_none());
+    }
+
+    #[test]
+    fn grouplist() {
+        let file = PasswdFile::new("grouplist", GROUP);
+        let mut group = Group::with_path(&file.0);
+        assert_eq!(group.getgrouplist(&user("alice", 1000)).unwrap(), vec![1000, 4, 27, 100]);
+        // the primary group isn't repeated even if the user is listed as its member.
+        assert_eq!(group.getgrouplist(&user("bob", 100)).unwrap(), vec![100]);
+        assert_eq!(group.getgrouplist(&user("nobody", 65534)).unwrap(), vec![65534]);
+    }
+
+    #[test]
+    fn system_database() {
+        assert_eq!(getgrgid(0).unwrap().name, "root");
+        assert_eq!(getgrnam("root").unwrap().group_id, 0);
+    }
+}
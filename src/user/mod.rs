@@ -1,9 +1,35 @@
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::io::Error as IOError;
-use std::io::Result as IOResult;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
 
+mod edit;
+mod group;
+mod names;
+mod shadow;
+
+pub use self::edit::Accounts;
+
+pub use self::group::getgrgid;
+pub use self::group::getgrnam;
+pub use self::group::getgrouplist;
+pub use self::group::Group;
+pub use self::group::GroupData;
+pub use self::names::Names;
+pub use self::shadow::getspnam;
+pub use self::shadow::today;
+pub use self::shadow::AccountState;
+pub use self::shadow::Shadow;
+pub use self::shadow::ShadowData;
+
+const PASSWD_PATH: &str = "/etc/passwd";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UserData {
     pub name: String,
     pub passwd: String,
@@ -14,79 +40,353 @@ pub struct UserData {
     pub pw_shell: String,
 }
 
-impl UserData{
-    
+impl fmt::Display for UserData {
+    /// Formats the entry as a line of the database.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{}:{}:{}:{}:{}:{}:{}",
+            self.name, self.passwd, self.user_id, self.group_id, self.comment, self.pw_dir, self.pw_shell,
+        )
+    }
 }
 
+/// Error of reading or editing any of the user databases.
+#[derive(Debug)]
 pub enum PasswdError {
     DoesNotExist,
+    /// The database can't be read by the current user, e.g. `/etc/shadow`.
+    PermissionDenied,
     IO(IOError),
-    IncorrectData(String),
+    /// Another process edits the databases.
+    Locked,
+    /// An edit would repeat a name or an id, or remove an entry in use.
+    Conflict(String),
+    /// A field of an entry to write can't be stored in the database.
+    InvalidField(String),
+    /// Number of the line starting from 1 and the line itself.
+    IncorrectData(usize, String),
+}
+
+impl fmt::Display for PasswdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswdError::DoesNotExist => write!(f, "entry is not found"),
+            PasswdError::PermissionDenied => write!(f, "permission denied"),
+            PasswdError::IO(e) => write!(f, "fail accessing database: {}", e),
+            PasswdError::Locked => write!(f, "database is locked by another process"),
+            PasswdError::Conflict(x) => write!(f, "{}", x),
+            PasswdError::InvalidField(x) => write!(f, "{}", x),
+            PasswdError::IncorrectData(number, line) => {
+                write!(f, "incorrect entry at line {}: `{}`", number, line)
+            },
+        }
+    }
+}
+
+impl Error for PasswdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PasswdError::IO(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<IOError> for PasswdError {
+    fn from(e: IOError) -> Self {
+        match e.kind() {
+            ErrorKind::PermissionDenied => PasswdError::PermissionDenied,
+            _ => PasswdError::IO(e),
+        }
+    }
 }
 
 type UserDataResult = Result<UserData, PasswdError>;
 
-fn getpwent() -> Result<impl Iterator<Item=UserDataResult>, IOError> {
-    let passwd_path = "/etc/passwd";
-    let fd = File::open(passwd_path)?;
-    let fd_reader = BufReader::new(fd);
-    let file_lines = fd_reader.lines().map(parse_line);
-    Ok(file_lines)
+/// Lines of a colon-separated database file.
+struct Entries {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    line_number: usize,
+    /// Set after a failure, so iteration ends instead of failing again on every read.
+    failed: bool,
 }
 
-fn setpwent() {
+impl Entries {
+    fn new(path: &Path) -> Self {
+        Entries { path: path.to_path_buf(), reader: None, line_number: 0, failed: false }
+    }
+
+    fn rewind(&mut self) -> Result<(), PasswdError> {
+        self.reader = Some(BufReader::new(File::open(&self.path)?));
+        self.line_number = 0;
+        self.failed = false;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.reader = None;
+        self.line_number = 0;
+        self.failed = false;
+    }
+
+    /// Parses the next line which isn't blank. The file is opened on the first read. An error of
+    /// opening or reading is returned once, then there are no entries until a rewind.
+    fn next<T>(&mut self, parse: fn(&[&str]) -> Option<T>) -> Option<Result<T, PasswdError>> {
+        if self.failed {
+            return None;
+        }
+        if self.reader.is_none() {
+            if let Err(e) = self.rewind() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+
+        let reader = self.reader.as_mut()?;
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(PasswdError::IO(e)));
+                },
+            }
+
+            let line = line.trim_end_matches('\n');
+            if !line.trim().is_empty() {
+                let data: Vec<&str> = line.split(':').collect();
+                return Some(parse(&data).ok_or_else(|| PasswdError::IncorrectData(self.line_number, line.to_owned())));
+            }
+        }
+    }
+
+    /// Looks for the first entry matching `predicate` from the beginning of the database.
+    fn find<T, F: Fn(&T) -> bool>(&mut self, parse: fn(&[&str]) -> Option<T>, predicate: F) -> Result<T, PasswdError> {
+        self.rewind()?;
+        while let Some(entry) = self.next(parse) {
+            let entry = entry?;
+            if predicate(&entry) {
+                return Ok(entry);
+            }
+        }
+        Err(PasswdError::DoesNotExist)
+    }
 }
 
-fn endpwent() {
+/// Cursor over a passwd database like `setpwent`, `getpwent` and `endpwent` of libc. The file
+/// is opened on the first read and reopened by `setpwent`.
+pub struct Passwd {
+    entries: Entries,
 }
 
-fn parse_line(line: IOResult<String>) -> UserDataResult {
-    let data = line.map_err(|x| PasswdError::IO(x))?;
-    let res = {
-        let data: Vec<&str> = data.split(':').take(7).collect();
-        parse_user_data(data)
-    };
-    res.ok_or_else(|| PasswdError::IncorrectData(data))
+impl Default for Passwd {
+    fn default() -> Self {
+        Self::with_path(PASSWD_PATH)
+    }
 }
 
-fn parse_user_data(data: Vec<&str>) -> Option<UserData> {
-    let user_id = data[2].parse().ok()?;
-    let group_id = data[3].parse().ok()?;
-    Some(UserData {
-        name: data[0].to_owned(),
-        passwd: data[1].to_owned(),
-        user_id: user_id,
-        group_id: group_id,
-        comment: data[4].to_owned(),
-        pw_dir: data[5].to_owned(),
-        pw_shell: data[6].to_owned(),
-    })
+impl Passwd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the database from `path` instead of `/etc/passwd`.
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        Passwd { entries: Entries::new(path.as_ref()) }
+    }
+
+    /// Rewinds to the first entry.
+    pub fn setpwent(&mut self) -> Result<(), PasswdError> {
+        self.entries.rewind()
+    }
+
+    /// Closes the database. The next read starts from the first entry.
+    pub fn endpwent(&mut self) {
+        self.entries.close()
+    }
+
+    /// Returns the next entry. Blank lines are skipped.
+    pub fn getpwent(&mut self) -> Option<UserDataResult> {
+        self.entries.next(parse_user_data)
+    }
+
+    pub fn getpwnam(&mut self, name: &str) -> UserDataResult {
+        self.entries.find(parse_user_data, |x| x.name == name)
+    }
+
+    pub fn getpwuid(&mut self, id: u32) -> UserDataResult {
+        self.entries.find(parse_user_data, |x| x.user_id == id)
+    }
 }
 
-pub fn getpwnam(name: &str) -> UserDataResult {
-    for line in getpwent().map_err(|x| PasswdError::IO(x))? {
-        let user = line?;
-        if user.name == name {
-            return Ok(user);
-        }
+impl Iterator for Passwd {
+    type Item = UserDataResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.getpwent()
     }
-    Err(PasswdError::DoesNotExist)
 }
 
-pub fn getpwuid(id: u32) -> UserDataResult {
-    let iter = getpwent().map_err(|x| PasswdError::IO(x))?;
-    for line in iter {
-        let user: UserData = line?;
-        if user.user_id == id {
-            return Ok(user);
-        }
+/// Opens `/etc/passwd` for iteration.
+pub fn getpwent() -> Result<Passwd, PasswdError> {
+    let mut passwd = Passwd::new();
+    passwd.setpwent()?;
+    Ok(passwd)
+}
+
+/// Parses exactly seven fields. A name and ids are required.
+fn parse_user_data(data: &[&str]) -> Option<UserData> {
+    match *data {
+        [name, passwd, user_id, group_id, comment, pw_dir, pw_shell] if !name.is_empty() => Some(UserData {
+            name: name.to_owned(),
+            passwd: passwd.to_owned(),
+            user_id: user_id.parse().ok()?,
+            group_id: group_id.parse().ok()?,
+            comment: comment.to_owned(),
+            pw_dir: pw_dir.to_owned(),
+            pw_shell: pw_shell.to_owned(),
+        }),
+        _ => None,
     }
-    Err(PasswdError::DoesNotExist)
 }
 
+pub fn getpwnam(name: &str) -> UserDataResult {
+    Passwd::new().getpwnam(name)
+}
+
+pub fn getpwuid(id: u32) -> UserDataResult {
+    Passwd::new().getpwuid(id)
+}
+
+/// Formats ids of `user` like the `id` command, e.g.
+/// `uid=1000(alice) gid=1000(alice) groups=1000(alice),27(sudo)`. Groups absent in `group` are
+/// printed without names.
+pub fn id(user: &UserData, group: &mut Group) -> Result<String, PasswdError> {
+    let ids = group.getgrouplist(user)?;
+    let mut format_group = |id: u32| match group.getgrgid(id) {
+        Ok(x) => Ok(format!("{}({})", id, x.name)),
+        Err(PasswdError::DoesNotExist) => Ok(id.to_string()),
+        Err(e) => Err(e),
+    };
+
+    let gid = format_group(user.group_id)?;
+    let mut groups = vec![];
+    for id in ids {
+        groups.push(format_group(id)?);
+    }
+    Ok(format!("uid={}({}) gid={} groups={}", user.user_id, user.name, gid, groups.join(",")))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+
+    /// Database file in the temporary directory removed on drop.
+    pub(super) struct PasswdFile(pub(super) PathBuf);
+
+    impl PasswdFile {
+        pub(super) fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("passwd_{}_{}", name, std::process::id()));
+            std::fs::write(&path, content).unwrap();
+            PasswdFile(path)
+        }
+    }
+
+    impl Drop for PasswdFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+
+alice:x:1000:1000:Alice,,,:/home/alice:/bin/zsh
+";
+
+    #[test]
+    fn cursor() {
+        let file = PasswdFile::new("cursor", PASSWD);
+        let mut passwd = Passwd::with_path(&file.0);
+        let names: Vec<String> = passwd.by_ref().map(|x| x.unwrap().name).collect();
+        assert_eq!(names, vec!["root", "daemon", "alice"]);
+        assert!(passwd.getpwent().is_none());
+
+        passwd.setpwent().unwrap();
+        assert_eq!(passwd.getpwent().unwrap().unwrap().name, "root");
+        passwd.endpwent();
+        assert_eq!(passwd.getpwent().unwrap().unwrap().name, "root");
+
+        let alice = passwd.getpwnam("alice").unwrap();
+        assert_eq!(alice, UserData {
+            name: "alice".to_owned(),
+            passwd: "x".to_owned(),
+            user_id: 1000,
+            group_id: 1000,
+            comment: "Alice,,,".to_owned(),
+            pw_dir: "/home/alice".to_owned(),
+            pw_shell: "/bin/zsh".to_owned(),
+        });
+        assert_eq!(passwd.getpwuid(1).unwrap().name, "daemon");
+        assert!(matches!(passwd.getpwuid(5), Err(PasswdError::DoesNotExist)));
+        assert!(matches!(passwd.getpwnam(""), Err(PasswdError::DoesNotExist)));
+    }
+
+    #[test]
+    fn incorrect_lines() {
+        let file = PasswdFile::new("incorrect", "root:x:0:0:root:/root:/bin/bash\n\nshort:x:1\n");
+        let mut passwd = Passwd::with_path(&file.0);
+        assert!(passwd.getpwent().unwrap().is_ok());
+        let e = passwd.getpwent().unwrap().unwrap_err();
+        assert!(matches!(&e, PasswdError::IncorrectData(3, x) if x == "short:x:1"), "{:?}", e);
+        assert_eq!(e.to_string(), "incorrect entry at line 3: `short:x:1`");
+        assert!(passwd.getpwent().is_none());
+
+        let parse = |x: &str| parse_user_data(&x.split(':').collect::<Vec<&str>>());
+        assert!(parse("a:x:0:0:::").is_some());
+        assert!(parse("a:x:0:0::::").is_none());
+        assert!(parse(":x:0:0:::").is_none());
+        assert!(parse("a:x:-1:0:::").is_none());
+        assert!(parse("a:x:0:root:::").is_none());
+    }
+
+    #[test]
+    fn absent_database() {
+        let mut passwd = Passwd::with_path("/nonexistent/passwd");
+        let e = passwd.getpwent().unwrap().unwrap_err();
+        assert!(matches!(e, PasswdError::IO(_)));
+        assert!(e.source().is_some());
+        assert!(passwd.getpwent().is_none());
+        assert!(matches!(passwd.getpwnam("root"), Err(PasswdError::IO(_))));
+
+        // iteration ends after the error.
+        let entries: Vec<UserDataResult> = Passwd::with_path("/nonexistent/passwd").collect();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_err());
+        assert_eq!(Group::with_path("/nonexistent/group").count(), 1);
+        assert_eq!(Shadow::with_path("/nonexistent/shadow").count(), 1);
+    }
+
+    #[test]
+    fn system_database() {
+        assert_eq!(getpwuid(0).unwrap().name, "root");
+        assert_eq!(getpwnam("root").unwrap().user_id, 0);
+        assert!(getpwent().unwrap().any(|x| x.is_ok_and(|x| x.user_id == 0)));
+    }
+
+    #[test]
+    fn id_output() {
+        let file = PasswdFile::new("id", "alice:x:1000:\nsudo:x:27:alice,bob\n");
+        let mut group = Group::with_path(&file.0);
+        let passwd_file = PasswdFile::new("id_passwd", PASSWD);
+        let mut passwd = Passwd::with_path(&passwd_file.0);
+        let alice = passwd.getpwnam("alice").unwrap();
+        assert_eq!(id(&alice, &mut group).unwrap(), "uid=1000(alice) gid=1000(alice) groups=1000(alice),27(sudo)");
+        let root = passwd.getpwuid(0).unwrap();
+        assert_eq!(id(&root, &mut group).unwrap(), "uid=0(root) gid=0 groups=0");
+    }
 }
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use super::Group;
+use super::Passwd;
+
+/// Resolves uids and gids to names and back. The databases are read once when the resolver is
+/// built, so a listing of many files or processes doesn't rescan them per lookup. Build a new
+/// resolver to see later changes.
+#[derive(Clone, Debug, Default)]
+pub struct Names {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+    uids: HashMap<String, u32>,
+    gids: HashMap<String, u32>,
+}
+
+impl Names {
+    /// Reads `/etc/passwd` and `/etc/group`.
+    pub fn load() -> Self {
+        Self::from_databases(Passwd::new(), Group::new())
+    }
+
+    /// Reads all entries of `passwd` and `group`. The first entry wins if ids or names repeat
+    /// like in `getpwuid` and `getpwnam`. Names only replace ids in output, so entries which
+    /// can't be read are skipped and an absent database has no names.
+    pub fn from_databases(passwd: Passwd, group: Group) -> Self {
+        let mut names = Names::default();
+        for user in passwd.filter_map(Result::ok) {
+            names.uids.entry(user.name.clone()).or_insert(user.user_id);
+            names.users.entry(user.user_id).or_insert(user.name);
+        }
+        for group in group.filter_map(Result::ok) {
+            names.gids.entry(group.name.clone()).or_insert(group.group_id);
+            names.groups.entry(group.group_id).or_insert(group.name);
+        }
+        names
+    }
+
+    pub fn user(&self, uid: u32) -> Option<&str> {
+        self.users.get(&uid).map(String::as_str)
+    }
+
+    pub fn group(&self, gid: u32) -> Option<&str> {
+        self.groups.get(&gid).map(String::as_str)
+    }
+
+    /// Name of the user or the uid itself if it has no name like ls(1) shows owners.
+    pub fn user_or_id(&self, uid: u32) -> String {
+        self.user(uid).map_or_else(|| uid.to_string(), str::to_owned)
+    }
+
+    pub fn group_or_id(&self, gid: u32) -> String {
+        self.group(gid).map_or_else(|| gid.to_string(), str::to_owned)
+    }
+
+    /// Uid of a user given by a name or by a number like `ps -u` and `find -user` accept. Names
+    /// are looked up first, so a user named by digits is found by its name.
+    pub fn uid(&self, user: &str) -> Option<u32> {
+        self.uids.get(user).copied().or_else(|| user.parse().ok())
+    }
+
+    pub fn gid(&self, group: &str) -> Option<u32> {
+        self.gids.get(group).copied().or_else(|| group.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::PasswdFile;
+
+    #[test]
+    fn lookup() {
+        let passwd = PasswdFile::new("names", "root:x:0:0::/root:/bin/sh\n1000:x:1001:100::/:/bin/sh\ntoor:x:0:0::/:/bin/sh\n");
+        let group = PasswdFile::new("names_group", "root:x:0:\nusers:x:100:1000\n");
+        let names = Names::from_databases(Passwd::with_path(&passwd.0), Group::with_path(&group.0));
+
+        assert_eq!(names.user(0), Some("root"));
+        assert_eq!(names.user(5), None);
+        assert_eq!(names.user_or_id(5), "5");
+        assert_eq!(names.group_or_id(100), "users");
+        assert_eq!(names.uid("toor"), Some(0));
+        assert_eq!(names.uid("1000"), Some(1001));
+        assert_eq!(names.uid("42"), Some(42));
+        assert_eq!(names.uid("nobody"), None);
+        assert_eq!(names.gid("users"), Some(100));
+
+        let broken = PasswdFile::new("names_broken", "+::::::\nbroken\nbob:x:1002:100::/:/bin/sh\n");
+        let names = Names::from_databases(Passwd::with_path(&broken.0), Group::with_path("/nonexistent/group"));
+        assert_eq!(names.uid("bob"), Some(1002));
+        assert_eq!(names.group_or_id(100), "100");
+        assert_eq!(Names::default().user_or_id(0), "0");
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::Entries;
+use super::PasswdError;
+
+const SHADOW_PATH: &str = "/etc/shadow";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Entry of the shadow database. Dates are days since 1970-01-01, periods are in days. Empty
+/// fields are `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowData {
+    pub name: String,
+    pub passwd: String,
+    /// Date of the last password change. Zero means the password must be changed.
+    pub last_change: Option<i64>,
+    /// Minimum and maximum age of a password.
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// Days before the password expires to warn the user.
+    pub warn: Option<i64>,
+    /// Days after the password expires when it's still accepted.
+    pub inactive: Option<i64>,
+    /// Date when the account expires.
+    pub expire: Option<i64>,
+}
+
+/// State of an account for a given day like shadow(5) describes it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountState {
+    Active,
+    /// The password field starts with `!`.
+    Locked,
+    /// The password field is empty, so logging in doesn't need a password.
+    NoPassword,
+    /// The password must be changed on the next login.
+    MustChange,
+    /// The password is expired but it can be changed on login.
+    PasswordExpired,
+    /// The password is expired and the inactivity period is over.
+    Inactive,
+    Expired,
+}
+
+impl ShadowData {
+    /// Date after which the password must be changed. `None` if it never expires.
+    pub fn password_expires(&self) -> Option<i64> {
+        match (self.last_change, self.max) {
+            (Some(last_change), Some(max)) if last_change > 0 => Some(last_change + max),
+            _ => None,
+        }
+    }
+
+    /// State of the account on `today`, the worst one if several apply.
+    pub fn state(&self, today: i64) -> AccountState {
+        if self.expire.is_some_and(|x| x > 0 && today >= x) {
+            return AccountState::Expired;
+        }
+        if self.passwd.starts_with('!') {
+            return AccountState::Locked;
+        }
+        if self.passwd.is_empty() {
+            return AccountState::NoPassword;
+        }
+        if self.last_change == Some(0) {
+            return AccountState::MustChange;
+        }
+        match (self.password_expires(), self.inactive) {
+            (Some(x), Some(inactive)) if today >= x + inactive => AccountState::Inactive,
+            (Some(x), _) if today >= x => AccountState::PasswordExpired,
+            _ => AccountState::Active,
+        }
+    }
+}
+
+/// Days since 1970-01-01 in the format of shadow dates.
+pub fn today() -> i64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs() / SECONDS_PER_DAY) as i64
+}
+
+type ShadowDataResult = Result<ShadowData, PasswdError>;
+
+/// Cursor over a shadow database like `setspent`, `getspent` and `endspent` of libc. Reading
+/// `/etc/shadow` usually needs root privileges, otherwise reads fail with `PermissionDenied`.
+pub struct Shadow {
+    entries: Entries,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Self::with_path(SHADOW_PATH)
+    }
+}
+
+impl Shadow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the database from `path` instead of `/etc/shadow`.
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        Shadow { entries: Entries::new(path.as_ref()) }
+    }
+
+    /// Rewinds to the first entry.
+    pub fn setspent(&mut self) -> Result<(), PasswdError> {
+        self.entries.rewind()
+    }
+
+    pub fn endspent(&mut self) {
+        self.entries.close()
+    }
+
+    /// Returns the next entry. Blank lines are skipped.
+    pub fn getspent(&mut self) -> Option<ShadowDataResult> {
+        self.entries.next(parse_shadow_data)
+    }
+
+    pub fn getspnam(&mut self, name: &str) -> ShadowDataResult {
+        self.entries.find(parse_shadow_data, |x| x.name == name)
+    }
+}
+
+impl Iterator for Shadow {
+    type Item = ShadowDataResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.getspent()
+    }
+}
+
+/// Parses an optional number. `None` if it's not a number.
+fn to_days(value: &str) -> Option<Option<i64>> {
+    match value {
+        "" => Some(None),
+        x => x.parse().ok().map(Some),
+    }
+}
+
+/// Parses exactly nine fields, the last one is reserved.
+fn parse_shadow_data(data: &[&str]) -> Option<ShadowData> {
+    match *data {
+        [name, passwd, last_change, min, max, warn, inactive, expire, _] if !name.is_empty() => Some(ShadowData {
+            name: name.to_owned(),
+            passwd: passwd.to_owned(),
+            last_change: to_days(last_change)?,
+            min: to_days(min)?,
+            max: to_days(max)?,
+            warn: to_days(warn)?,
+            inactive: to_days(inactive)?,
+            expire: to_days(expire)?,
+        }),
+        _ => None,
+    }
+}
+
+pub fn getspnam(name: &str) -> ShadowDataResult {
+    Shadow::new().getspnam(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::PasswdFile;
+    use std::io::Error as IOError;
+    use std::io::ErrorKind;
+
+    const SHADOW: &str = "root:$6$salt$hash:19000:0:99999:7:::
+daemon:*:19000:0:99999:7:::
+locked:!$6$salt$hash:19000:0:99999:7:::
+";
+
+    fn shadow(passwd: &str, last_change: Option<i64>, max: Option<i64>, inactive: Option<i64>, expire: Option<i64>) -> ShadowData {
+        ShadowData {
+            name: "alice".to_owned(),
+            passwd: passwd.to_owned(),
+            last_change,
+            min: None,
+            max,
+            warn: None,
+            inactive,
+            expire,
+        }
+    }
+
+    #[test]
+    fn cursor() {
+        let file = PasswdFile::new("shadow", SHADOW);
+        let mut shadow = Shadow::with_path(&file.0);
+        let names: Vec<String> = shadow.by_ref().map(|x| x.unwrap().name).collect();
+        assert_eq!(names, vec!["root", "daemon", "locked"]);
+
+        let root = shadow.getspnam("root").unwrap();
+        assert_eq!((root.last_change, root.min, root.max, root.warn), (Some(19000), Some(0), Some(99999), Some(7)));
+        assert_eq!((root.inactive, root.expire), (None, None));
+        assert!(matches!(shadow.getspnam("alice"), Err(PasswdError::DoesNotExist)));
+
+        assert!(parse_shadow_data(&["a", "*", "", "", "", "", "", ""]).is_none());
+        assert!(parse_shadow_data(&["a", "*", "day", "", "", "", "", "", ""]).is_none());
+    }
+
+    #[test]
+    fn account_state() {
+        assert_eq!(shadow("hash", Some(100), Some(30), None, None).state(120), AccountState::Active);
+        assert_eq!(shadow("hash", Some(100), Some(30), None, None).state(130), AccountState::PasswordExpired);
+        assert_eq!(shadow("hash", Some(100), Some(30), Some(5), None).state(134), AccountState::PasswordExpired);
+        assert_eq!(shadow("hash", Some(100), Some(30), Some(5), None).state(135), AccountState::Inactive);
+        assert_eq!(shadow("hash", Some(100), None, Some(5), None).state(1000), AccountState::Active);
+        assert_eq!(shadow("hash", Some(0), Some(30), None, None).state(1), AccountState::MustChange);
+        assert_eq!(shadow("!hash", Some(100), None, None, None).state(1), AccountState::Locked);
+        assert_eq!(shadow("", Some(100), None, None, None).state(1), AccountState::NoPassword);
+        assert_eq!(shadow("!hash", Some(100), None, None, Some(50)).state(50), AccountState::Expired);
+        assert_eq!(shadow("hash", Some(100), Some(30), None, None).password_expires(), Some(130));
+        assert!(today() > 19000);
+    }
+
+    #[test]
+    fn permission_denied() {
+        let e = PasswdError::from(IOError::from(ErrorKind::PermissionDenied));
+        assert!(matches!(e, PasswdError::PermissionDenied));
+        assert_eq!(e.to_string(), "permission denied");
+    }
+}